@@ -0,0 +1,57 @@
+// A server for versus over the network: the game is started with --connect=address on both ends,
+// and the server pairs them up and picks what they play
+
+use tetris::tetris::net::{Server, DEFAULT_ADDRESS};
+use tetris::tetris::randomizer;
+use tetris::tetris::scoring;
+use tetris::tetris::mode::GameMode;
+use tetris::tetris::settings::Settings;
+
+
+// options are given as --name=value, like the game's
+fn main() {
+	let mut address = DEFAULT_ADDRESS.to_string();
+	let mut seed = None;
+	let mut randomizer = "7bag".to_string();
+	let mut scoring = "guideline".to_string();
+	let mut settings = Settings::default();
+	let parsed: Result<(), String> = std::env::args().skip(1).try_for_each(|arg| {
+		let (name, value) = arg.strip_prefix("--")
+			.and_then(|arg| arg.split_once('='))
+			.ok_or_else(|| format!("Expected an option of the form --name=value, got '{}'", arg))?;
+		match name {
+			"address" => address = value.to_string(),
+			"seed" => {
+				seed = Some(value.parse()
+					.map_err(|_| format!("The seed must be a whole number between 0 and {}, got '{}'", u64::MAX, value))?);
+			}
+			"randomizer" => {
+				randomizer = randomizer::from_name(value)
+					.ok_or_else(|| format!("Unknown randomizer '{}'. Try 7bag, 14bag, uniform or nes", value))?
+					.name();
+			}
+			"scoring" => {
+				scoring = scoring::from_name(value)
+					.ok_or_else(|| format!("Unknown scoring '{}'. Try guideline or nes", value))?
+					.name();
+			}
+			"mode" => {
+				// dig mode is for one player, the garbage the opponent sends would count towards digging out
+				settings.mode = GameMode::from_name(value)
+					.filter(|mode| mode.dig_rows().is_none())
+					.ok_or_else(|| format!("Unknown mode '{}'. Try marathon, endless, sprint, ultra or zen", value))?;
+			}
+			_ => return Err(format!("Unknown option '--{}'", name)),
+		}
+		Ok(())
+	});
+	let served = parsed.and_then(|()| {
+		let server = Server::bind(&address, seed, randomizer, scoring, settings)?;
+		println!("Waiting for players on {}", server.local_addr()?);
+		server.serve()
+	});
+	if let Err(e) = served {
+		eprintln!("{}", e);
+		std::process::exit(2);
+	}
+}
@@ -7,16 +7,327 @@ use termion::raw::IntoRawMode;
 use std::io::stdout;
 
 
-mod tetris;
-use crate::tetris::game::Game;
+use tetris::tetris::{self as game, game::Game, VersusResult};
+use tetris::tetris::randomizer::{self, Randomizer};
+use tetris::tetris::scoring::{self, Scoring};
+use tetris::tetris::gravity::GravityCurve;
+use tetris::tetris::mode::GameMode;
+use tetris::tetris::keybindings::KeyBindings;
+use tetris::tetris::settings::{Settings, LockReset, INFINITE_SOFT_DROP_FACTOR, MAX_NEXT_QUEUE_LENGTH, MIN_BUFFER_ROWS};
+use tetris::tetris::replay::Replay;
+use tetris::tetris::save::SaveGame;
+use tetris::tetris::engine::Engine;
+use tetris::tetris::net;
 
+use std::path::PathBuf;
+
+
+// command line options, given as --name=value
+struct Options {
+	randomizer: Box<dyn Randomizer>,
+	scoring: Box<dyn Scoring>,
+	seed: u64,
+	settings: Settings,
+	key_bindings: KeyBindings,
+	opponent_key_bindings: Option<KeyBindings>, // the second player's keys, in versus
+	record_path: Option<PathBuf>, // where to save the replay of the game
+	replay: Option<Replay>, // a replay to play back instead of playing
+	save_path: Option<PathBuf>, // where a game that was quit is saved, and picked up from on the next launch
+	connect: Option<String>, // the address of a server to play versus over the network on
+	watch: Option<String>, // the address of a server to watch a game over the network on
+}
+
+impl Options {
+	fn from_args() -> Result<Self, String> {
+		let mut key_bindings_path = None;
+		let mut players = 1;
+		let (mut dig_rows, mut messiness) = (None, None);
+		let mut options = Self {
+			randomizer: Box::new(randomizer::Bag::default()),
+			scoring: Box::new(scoring::Guideline::default()),
+			seed: rand::random(),
+			settings: Settings::default(),
+			key_bindings: KeyBindings::default(),
+			opponent_key_bindings: None,
+			record_path: None,
+			replay: None,
+			save_path: SaveGame::default_path(),
+			connect: None,
+			watch: None,
+		};
+		for arg in std::env::args().skip(1) {
+			let (name, value) = arg.strip_prefix("--")
+				.and_then(|arg| arg.split_once('='))
+				.ok_or_else(|| format!("Expected an option of the form --name=value, got '{}'", arg))?;
+			match name {
+				"randomizer" => {
+					options.randomizer = randomizer::from_name(value)
+						.ok_or_else(|| format!("Unknown randomizer '{}'. Try 7bag, 14bag, uniform or nes", value))?;
+				}
+				"scoring" => {
+					options.scoring = scoring::from_name(value)
+						.ok_or_else(|| format!("Unknown scoring '{}'. Try guideline or nes", value))?;
+				}
+				"seed" => {
+					options.seed = value.parse()
+						.map_err(|_| format!("The seed must be a whole number between 0 and {}, got '{}'", u64::MAX, value))?;
+				}
+				"next" => {
+					options.settings.next_queue_length = value.parse()
+						.ok()
+						.filter(|length| (1..=MAX_NEXT_QUEUE_LENGTH).contains(length))
+						.ok_or_else(|| format!("The next queue length must be between 1 and {}, got '{}'", MAX_NEXT_QUEUE_LENGTH, value))?;
+				}
+				"mode" => {
+					options.settings.mode = GameMode::from_name(value)
+						.ok_or_else(|| format!("Unknown mode '{}'. Try marathon, endless, sprint, ultra or zen", value))?;
+				}
+				"garbage" => {
+					dig_rows = Some(value.parse()
+						.ok()
+						.filter(|rows| *rows > 0)
+						.ok_or_else(|| format!("The rows of garbage must be a positive whole number, got '{}'", value))?);
+				}
+				"messiness" => {
+					messiness = Some(value.parse()
+						.ok()
+						.filter(|percent| *percent <= 100)
+						.ok_or_else(|| format!("The messiness must be a percentage between 0 and 100, got '{}'", value))?);
+				}
+				"buffer" => {
+					options.settings.buffer_rows = value.parse()
+						.ok()
+						.filter(|rows| *rows >= MIN_BUFFER_ROWS)
+						.ok_or_else(|| format!("The buffer must be at least {} rows, got '{}'", MIN_BUFFER_ROWS, value))?;
+				}
+				"lock-delay" => {
+					let millis = value.parse()
+						.map_err(|_| format!("The lock delay must be a whole number of milliseconds, got '{}'", value))?;
+					options.settings.lock_delay = std::time::Duration::from_millis(millis);
+				}
+				"lock-reset" => {
+					options.settings.lock_reset = LockReset::from_name(value)
+						.ok_or_else(|| format!("Unknown lock reset '{}'. Try move, step or none", value))?;
+				}
+				"level" => {
+					options.settings.start_level = value.parse()
+						.map_err(|_| format!("The level must be a whole number, got '{}'", value))?;
+				}
+				"lines-per-level" => {
+					options.settings.lines_per_level = value.parse()
+						.ok()
+						.filter(|lines| *lines > 0)
+						.ok_or_else(|| format!("The lines per level must be a positive whole number, got '{}'", value))?;
+				}
+				"gravity" => {
+					options.settings.gravity = GravityCurve::from_name(value)
+						.ok_or_else(|| format!("Unknown gravity '{}'. Try guideline, nes, 20g or a list of milliseconds per row like 1000,800,600", value))?;
+				}
+				"das" => {
+					let millis = value.parse()
+						.map_err(|_| format!("The DAS must be a whole number of milliseconds, got '{}'", value))?;
+					options.settings.das = std::time::Duration::from_millis(millis);
+				}
+				"arr" => {
+					let millis = value.parse()
+						.map_err(|_| format!("The ARR must be a whole number of milliseconds, got '{}'", value))?;
+					options.settings.arr = std::time::Duration::from_millis(millis);
+				}
+				"sdf" => {
+					options.settings.soft_drop_factor = match value {
+						"inf" => INFINITE_SOFT_DROP_FACTOR,
+						_ => value.parse()
+							.ok()
+							.filter(|factor| *factor > 0)
+							.ok_or_else(|| format!("The soft drop factor must be a positive whole number or inf, got '{}'", value))?,
+					};
+				}
+				"players" => {
+					players = value.parse()
+						.ok()
+						.filter(|players| (1..=2).contains(players))
+						.ok_or_else(|| format!("There can be 1 or 2 players, got '{}'", value))?;
+				}
+				"keys" => key_bindings_path = Some(PathBuf::from(value)),
+				"record" => options.record_path = Some(PathBuf::from(value)),
+				"replay" => options.replay = Some(Replay::load(&PathBuf::from(value))?),
+				"save" => options.save_path = Some(PathBuf::from(value)),
+				"connect" => options.connect = Some(value.to_string()),
+				"watch" => options.watch = Some(value.to_string()),
+				_ => return Err(format!("Unknown option '--{}'", name)),
+			}
+		}
+		if let GameMode::Dig { rows, messiness: mode_messiness } = &mut options.settings.mode {
+			*rows = dig_rows.unwrap_or(*rows);
+			*mode_messiness = messiness.unwrap_or(*mode_messiness);
+		} else if dig_rows.is_some() || messiness.is_some() {
+			return Err("--garbage and --messiness only go with --mode=dig".to_string());
+		}
+		if options.connect.is_some() {
+			if players == 2 {
+				return Err("A game over the network is for one player on each end".to_string());
+			}
+			if options.record_path.is_some() || options.replay.is_some() {
+				return Err("A game over the network can't be recorded or replayed".to_string());
+			}
+		}
+		if options.watch.is_some() && (options.connect.is_some() || players == 2 || options.record_path.is_some() || options.replay.is_some()) {
+			return Err("Watching a game over the network can't be combined with playing, recording or replaying one".to_string());
+		}
+		// the garbage the opponent sends would count towards digging out
+		if players == 2 && options.settings.mode.dig_rows().is_some() {
+			return Err("Dig mode is for one player".to_string());
+		}
+		if players == 2 {
+			// the garbage one player gets from the other isn't part of their inputs, so it can't be replayed
+			if options.record_path.is_some() || options.replay.is_some() {
+				return Err("A versus game can't be recorded or replayed".to_string());
+			}
+			let [first, second] = KeyBindings::load_versus(key_bindings_path.as_deref())?;
+			options.key_bindings = first;
+			options.opponent_key_bindings = Some(second);
+		} else {
+			options.key_bindings = KeyBindings::load(key_bindings_path.as_deref())?;
+		}
+		// a replay plays with the seed and settings it was recorded with
+		if let Some(replay) = &options.replay {
+			options.randomizer = replay.make_randomizer()?;
+			options.scoring = replay.make_scoring()?;
+			options.seed = replay.seed;
+			options.settings = replay.settings.clone();
+		}
+		Ok(options)
+	}
+}
 
 fn main() {
 	std::env::set_var("RUST_BACKTRACE", "1");
+	let mut options = match Options::from_args() {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{}", e);
+			std::process::exit(2);
+		}
+	};
+	// a game over the network is the one the server picks, played with this player's own handling
+	let remote = options.connect.as_deref().map(|address| {
+		println!("Waiting for an opponent on {}", address);
+		let joined = net::join(address).and_then(|(connection, setup)| {
+			let player = setup.player.ok_or("The server only has room to watch")?;
+			if setup.settings.mode.dig_rows().is_some() {
+				return Err("The server picked dig mode, which is for one player".to_string());
+			}
+			Ok((connection, player, setup.make_randomizer()?, setup.make_scoring()?, setup))
+		});
+		let (connection, player, randomizer, scoring, setup) = joined.unwrap_or_else(|e| {
+			eprintln!("{}", e);
+			std::process::exit(2);
+		});
+		options.randomizer = randomizer;
+		options.scoring = scoring;
+		options.seed = setup.seed;
+		let handling = options.settings.clone();
+		options.settings = setup.settings;
+		options.settings.das = handling.das;
+		options.settings.arr = handling.arr;
+		options.settings.soft_drop_factor = handling.soft_drop_factor;
+		(connection, player)
+	});
+	// watching a game over the network shows the boards both players send, on two boards made from the server's game
+	let spectator = options.watch.as_deref().map(|address| {
+		println!("Waiting for a game to watch on {}", address);
+		let watched = net::watch(address).and_then(|(connection, setup)| {
+			Ok((connection, setup.make_randomizer()?, setup.make_scoring()?, setup))
+		});
+		let (connection, randomizer, scoring, setup) = watched.unwrap_or_else(|e| {
+			eprintln!("{}", e);
+			std::process::exit(2);
+		});
+		options.randomizer = randomizer;
+		options.scoring = scoring;
+		options.seed = setup.seed;
+		options.settings = setup.settings;
+		// only the keys that quit do anything, and they're the first player's
+		options.opponent_key_bindings = Some(options.key_bindings.clone());
+		connection
+	});
+	// a game that was quit is picked up again, unless a replay is watched or two players play
+	let single_player = options.replay.is_none() && options.opponent_key_bindings.is_none() && remote.is_none();
+	let saved = match &options.save_path {
+		Some(path) if single_player && path.exists() => {
+			let engine = SaveGame::load(path, &options.settings).unwrap_or_else(|e| {
+				eprintln!("{}", e);
+				std::process::exit(2);
+			});
+			Some(engine)
+		}
+		_ => None,
+	};
+
 	let stdout = stdout().into_raw_mode().unwrap();
 	let stdin = termion::async_stdin();
 
-	let mut game = tetris::Tetris::new(10, 20, stdout, stdin);
-	game.reset();
+	// both players get the same pieces
+	let opponent = options.opponent_key_bindings.map(|key_bindings| {
+		let randomizer = randomizer::from_name(&options.randomizer.name()).expect("A randomizer can be made by its name");
+		let scoring = scoring::from_name(&options.scoring.name()).expect("A scoring can be made by its name");
+		(Engine::new(randomizer, scoring, options.seed, options.settings.clone()), key_bindings)
+	});
+	let mut game = game::Tetris::new(stdout, stdin, options.key_bindings, options.randomizer, options.scoring, options.seed, options.settings);
+	if let Some((engine, key_bindings)) = opponent {
+		game = game.with_opponent(engine, key_bindings);
+	}
+	if let Some((connection, player)) = remote {
+		game = game.with_remote(connection, player);
+	}
+	if let Some(connection) = spectator {
+		game = game.with_spectator(connection);
+	}
+	if let Some(replay) = &options.replay {
+		game = game.with_playback(replay.playback());
+	}
+	match saved {
+		Some(engine) => game = game.with_engine(engine),
+		None => game.reset(),
+	}
 	game.run();
+	if let Some(result) = game.remote_result() {
+		print!("{}\n\r", result);
+	} else if game.engines().count() > 1 {
+		if let (Some((index, outcome)), Some(result)) = (game.ended_first(), game.versus_result()) {
+			print!("Player {}: {}\n\r", index + 1, outcome);
+			match result {
+				VersusResult::Winner(winner) => print!("Player {} wins\n\r", winner + 1),
+				VersusResult::Draw => print!("Draw\n\r"),
+			}
+		}
+	} else {
+		if let Some(outcome) = game.engine().outcome() {
+			print!("{}\n\r", outcome);
+		}
+		for line in game.engine().mode().hud(game.engine()) {
+			print!("{}\n\r", line);
+		}
+	}
+	print!("Seed: {}\n\r", game.seed());
+	// the save is only replaced once the game is left, so it isn't lost if the game crashes on the way.
+	// quitting saves the game over it and a game that ended leaves none
+	if let Some(path) = options.save_path.as_ref().filter(|_| single_player) {
+		if !game.engine().has_ended() {
+			match SaveGame::save(game.engine(), path) {
+				Ok(()) => print!("Game saved to {}\n\r", path.display()),
+				Err(e) => eprint!("{}\n\r", e),
+			}
+		} else if path.exists() {
+			if let Err(e) = std::fs::remove_file(path) {
+				eprint!("Can't remove the saved game {}: {}\n\r", path.display(), e);
+			}
+		}
+	}
+	if let Some(path) = options.record_path {
+		match game.engine().replay().save(&path) {
+			Ok(()) => print!("Replay saved to {}\n\r", path.display()),
+			Err(e) => eprint!("{}\n\r", e),
+		}
+	}
 }
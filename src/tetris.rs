@@ -1,473 +1,527 @@
 pub mod game;
+pub mod randomizer;
+pub mod settings;
+pub mod gravity;
+pub mod scoring;
+pub mod input;
+pub mod keybindings;
+pub mod engine;
+pub mod clock;
+pub mod replay;
+pub mod save;
+pub mod mode;
+pub mod garbage;
+pub mod net;
+pub mod cell;
+pub mod tetromino;
+mod held_keys;
 mod v2;
-mod cell;
 mod direction;
-mod tetromino;
-
-use std::fmt;
 
 use game::Game;
 use v2::*;
 use cell::*;
-use direction::*;
-use tetromino::{Tetromino, Shape};
-
+use tetromino::Tetromino;
+use randomizer::Randomizer;
+use scoring::{Scoring, ScoreEvent};
+use keybindings::{KeyBindings, Action};
+use held_keys::HeldKeys;
+use settings::{Settings, MIN_BUFFER_ROWS};
+use engine::{Engine, GameEvent, Outcome, Snapshot, TICK};
+use net::{Connection, Message};
+use replay::Playback;
+use clock::{Clock, SystemClock};
+
+use std::cmp::Ordering;
 use std::io::{Read, Write};
 use std::time;
 
+use serde::{Serialize, Deserialize};
+
 
 const USER_INPUT_INTERVAL : time::Duration = time::Duration::from_millis(5);
-const GAME_GRAVITY_INTERVAL : time::Duration = time::Duration::from_secs(1);
 const GAME_DRAW_INTERVAL : time::Duration = time::Duration::from_millis(1000 / 24);
+// the space between boards side by side
+const BOARD_GAP: usize = 4;
+// how often the board is sent to the opponent at most, in a game over the network
+const BOARD_SEND_INTERVAL : time::Duration = time::Duration::from_millis(50);
+
+// the choices of the pause menu
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MenuItem {
+	Resume,
+	Restart,
+	Quit,
+}
 
-const T_SPIN_SCORE: u32 = 400;
+impl MenuItem {
+	fn name(&self) -> &'static str {
+		match self {
+			Self::Resume => "Resume",
+			Self::Restart => "Restart",
+			Self::Quit => "Quit",
+		}
+	}
+}
 
 
-#[derive(Clone, PartialEq, Eq)]
-struct Piece {
-	pos: V2,
-	points: [V2; 4],
-	data: Tetromino,
-	cell_value: Cell,
+// a board and the keys that play it
+struct Player {
+	engine: Engine,
+	key_bindings: KeyBindings,
+	held_keys: HeldKeys,
 }
 
-impl fmt::Debug for Piece {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//        f.debug_struct("Piece")
-//         .field("points", &self.points)
-//         .finish()
-         write!(f, "Points: [")?;
-         for p in self.points {
-         	write!(f, "{}", p)?;
-         }
-         write!(f, "]")
-    }
-}
+impl Player {
+	fn new(engine: Engine, key_bindings: KeyBindings) -> Self {
+		Self { engine, key_bindings, held_keys: HeldKeys::default() }
+	}
 
-impl Default for Piece {
-	fn default() -> Self {
-		Self {
-			pos: V2::new(0,0),
-			points: [V2::new(0,0); 4],
-			data: Tetromino::from(Shape::I),
-			cell_value: Cell::Ghost(Shape::I),
-		}
+	// lets go of the held keys, so their moves stop repeating
+	fn release_keys(&mut self) {
+		self.held_keys.clear();
+		self.engine.release_moves();
 	}
 }
 
-impl Piece {
-	pub fn new(pos: V2, cell_value: Cell) -> Self {
-		let data = Tetromino::from(cell_value.unwrap());
-		let offsets = data.current_offsets();
-		Self {
-			pos,
-			points: [pos + offsets[0], pos + offsets[1], pos + offsets[2], pos + offsets[3]],
-			data,
-			cell_value,
-		}
-	}
+// an opponent playing on another machine, through a server
+struct Remote {
+	connection: Connection,
+	player: usize, // the index the server gave this player
+	opponent: Option<Engine>, // made from the last board the opponent sent
+	inputs_sent: usize,
+	board_changed: bool,
+	next_board_time: time::Duration,
+	result: Option<String>, // how the game ended, once it has
+	time_up_scores: [Option<u32>; 2], // the score of each player whose time ran out, by index
+}
 
-	pub fn clone_as_ghost(&self) -> Self {
-		let mut copy = self.clone();
-		copy.cell_value.to_ghost();
-		copy
+impl Remote {
+	// takes in how a player's game ended, and returns whether that decided the game
+	fn end(&mut self, player: usize, outcome: Outcome, score: u32) -> bool {
+		if self.result.is_some() {
+			return true;
+		}
+		let Some(result) = VersusResult::over_network(&mut self.time_up_scores, player, outcome, score) else {
+			return false;
+		};
+		self.result = Some(match result {
+			VersusResult::Winner(winner) if winner == self.player => "You win",
+			VersusResult::Winner(_) => "You lose",
+			VersusResult::Draw => "Draw",
+		}.to_string());
+		true
 	}
+}
 
-	pub fn clone_as_full(&self) -> Self {
-		let mut copy = self.clone();
-		copy.cell_value.to_full();
-		copy
-	}
+// someone watching a game over the network, who sees the players' boards as they send them
+struct Spectator {
+	connection: Connection,
+	result: Option<String>, // how the game ended, once it has
+	time_up_scores: [Option<u32>; 2],
+}
 
-	pub fn translate(&mut self, v: V2) {
-		self.pos = self.pos + v;
-		self.points.iter_mut().for_each(|p| *p = *p + v);
+impl Spectator {
+	// takes in how a player's game ended, and returns whether that decided the game
+	fn end(&mut self, player: usize, outcome: Outcome, score: u32) -> bool {
+		let Some(result) = VersusResult::over_network(&mut self.time_up_scores, player, outcome, score) else {
+			return false;
+		};
+		self.result = Some(match result {
+			VersusResult::Winner(winner) => format!("Player {} wins", winner + 1),
+			VersusResult::Draw => "Draw".to_string(),
+		});
+		true
 	}
+}
 
-	// moves the coordinates of the piece by adding a V2 to each
-	pub fn translated(&self, v: V2) -> Self {
-		let mut copy = self.clone();
-		copy.translate(v);
-		copy
-	}
+// how a versus game came out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VersusResult {
+	Winner(usize),
+	Draw,
+}
 
-	fn rotated(&self, is_left: bool) -> Self {
-		let mut data = self.data.clone();
-		if is_left {
-			data.rotate_left();
+impl VersusResult {
+	// the first game to end decides it: finishing the mode wins and topping out loses.
+	// when the time ran out, it did for both players, and the higher score wins
+	fn decide(first: usize, outcome: Outcome, scores: [u32; 2]) -> Self {
+		if outcome.is_time_up() {
+			match scores[0].cmp(&scores[1]) {
+				Ordering::Greater => Self::Winner(0),
+				Ordering::Less => Self::Winner(1),
+				Ordering::Equal => Self::Draw,
+			}
+		} else if outcome.is_win() {
+			Self::Winner(first)
 		} else {
-			data.rotate_right();
-		}
-		let pos = self.pos;
-		let offsets = data.current_offsets();
-		Self {
-			pos,
-			points: [pos + offsets[0], pos + offsets[1], pos + offsets[2], pos + offsets[3]],
-			data,
-			cell_value: self.cell_value,
+			Self::Winner(1 - first)
 		}
 	}
 
-	pub fn rotated_left(&self) -> Self {
-		self.rotated(true)
+	// decides a game over the network from the end of one player's game, which each player sends on its own.
+	// when the time ran out, it waits for the other player's time to run out too, for their score
+	fn over_network(time_up_scores: &mut [Option<u32>; 2], player: usize, outcome: Outcome, score: u32) -> Option<Self> {
+		if !outcome.is_time_up() {
+			return Some(Self::decide(player, outcome, [0, 0]));
+		}
+		time_up_scores[player] = Some(score);
+		let [Some(first), Some(second)] = *time_up_scores else {
+			return None;
+		};
+		Some(Self::decide(player, outcome, [first, second]))
 	}
+}
 
-	pub fn rotated_right(&self) -> Self {
-		self.rotated(false)
+// a board sent from the other end, drawn as it comes. one that isn't the size of the boards in this game is dropped
+fn remote_engine(snapshot: Snapshot, settings: &Settings) -> Option<Engine> {
+	let board = snapshot.settings();
+	let buffer_rows = |settings: &Settings| settings.buffer_rows.max(MIN_BUFFER_ROWS);
+	if board.width != settings.width || board.height != settings.height || buffer_rows(board) != buffer_rows(settings) {
+		return None;
 	}
+	Engine::from_snapshot(snapshot).ok()
 }
 
-const FALLER_INDEX: usize = 0;
-const GHOST_INDEX: usize = 1;
-
+// plays an Engine in the terminal: reads keys from input and draws the board to output.
+// with an opponent, two engines play side by side and send each other garbage
 pub struct Tetris<R, W> {
-	width: usize, // todo can use u8
-	height: usize, // todo can use u8
-	map: Vec<Cell>,
-	score: u32,
-	game_over: bool,
-
-	pieces: [Piece; 2], // unfortunately I have to get to pieces via index instead of reference. Damn you rust
-//	piece: RefCell<Piece>, // data of the currently falling piece
-//	ghost: RefCell<Piece>, // data of the future location of the currently falling piece
-
-	now: time::Instant,
-	start_time: time::Instant,
-	next_frame_time: time::Instant,
-	next_gravity_time: time::Instant,
-	next_input_time: time::Instant,
+	players: Vec<Player>,
+	remote: Option<Remote>, // the opponent in a game over the network
+	spectator: Option<Spectator>, // where the boards come from when watching a game over the network
+	ended_first: Option<(usize, Outcome)>, // the player whose game ended first and how, which decides a versus game
+	quit: bool,
+
+	clock: Box<dyn Clock>,
+	now: time::Duration,
+	last_step_time: time::Duration, // when the engine was last stepped to
+	next_frame_time: time::Duration,
+	next_input_time: time::Duration,
 	display_changed: bool,
 
 	output: W,
 	input: R,
+	input_decoder: input::Decoder,
+	playback: Option<Playback>, // plays a replay instead of the keys, which can only quit then
+	playback_since_tick: time::Duration,
+	paused: Option<usize>, // the selected item of the pause menu, while the game is paused
 }
 
-
-// self.piece functionality
 impl<R: Read, W: Write> Tetris<R, W> {
-	fn faller(&self) -> &Piece {
-		&self.pieces[FALLER_INDEX]
+	pub fn new(output : W, input : R, key_bindings: KeyBindings, randomizer: Box<dyn Randomizer>, scoring: Box<dyn Scoring>, seed: u64, settings: Settings) -> Self {
+		let clock = SystemClock::default();
+		let now = clock.now();
+		Self {
+			players: vec![Player::new(Engine::new(randomizer, scoring, seed, settings), key_bindings)],
+			remote: None,
+			spectator: None,
+			ended_first: None,
+			quit: false,
+			clock: Box::new(clock),
+			now,
+			last_step_time: now,
+			next_frame_time: now,
+			next_input_time: now,
+			display_changed: true,
+			output,
+			input,
+			input_decoder: Default::default(),
+			playback: None,
+			playback_since_tick: time::Duration::ZERO,
+			paused: None,
+		}
 	}
 
-	fn ghost(&self) -> &Piece {
-		&self.pieces[GHOST_INDEX]
+	// plays on another clock than the wall clock
+	pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+		self.clock = Box::new(clock);
+		self
 	}
 
-	fn faller_mut(&mut self) -> &mut Piece {
-		&mut self.pieces[FALLER_INDEX]
+	// plays a game that was already started, like one picked up from a save
+	pub fn with_engine(mut self, engine: Engine) -> Self {
+		self.players[0] = Player::new(engine, self.players[0].key_bindings.clone());
+		self
 	}
 
-	fn ghost_mut(&mut self) -> &mut Piece {
-		&mut self.pieces[GHOST_INDEX]
+	// adds a second board next to the first, played with other keys. the first game to end decides who wins
+	pub fn with_opponent(mut self, engine: Engine, key_bindings: KeyBindings) -> Self {
+		self.players.push(Player::new(engine, key_bindings));
+		self
 	}
 
-	fn fill_piece_cells_with(&mut self, piece_inedx: usize, value: Cell) {
-		for point in self.pieces[piece_inedx].points {
-			*self.at_mut(point) = value;
-		}
+	// plays against an opponent on another machine, through a server that was joined with net::join.
+	// the engine has to be made from the seed and settings the server sent
+	pub fn with_remote(mut self, connection: Connection, player: usize) -> Self {
+		self.remote = Some(Remote {
+			connection,
+			player,
+			opponent: None,
+			inputs_sent: 0,
+			board_changed: true,
+			next_board_time: time::Duration::ZERO,
+			result: None,
+			time_up_scores: [None; 2],
+		});
+		self
 	}
 
-	fn remove_piece(&mut self, piece_inedx: usize) {
-		self.fill_piece_cells_with(piece_inedx, Cell::Empty);
+	// watches a game over the network, through a server that was joined with net::watch. the boards of both players
+	// are replaced by the ones they send, so the game needs an opponent, both made from the setup the server sent
+	pub fn with_spectator(mut self, connection: Connection) -> Self {
+		self.spectator = Some(Spectator { connection, result: None, time_up_scores: [None; 2] });
+		self
 	}
 
-	fn insert_piece(&mut self, piece_inedx: usize) {
-		self.fill_piece_cells_with(piece_inedx, self.pieces[piece_inedx].cell_value);
+	// shows a replay being played back. the engine has to be made from the replay's seed and settings
+	pub fn with_playback(mut self, playback: Playback) -> Self {
+		self.playback = Some(playback);
+		self
 	}
 
-	fn remove_ghost(&mut self) {
-		let g = &self.ghost();
-		if g.pos != self.faller().pos {
-			for p in g.points {
-				let c = self.at_mut(p);
-				if let Cell::Ghost(_) = *c {
-					*c = Cell::Empty;
-				}
-			}
-		}
+	// the player a key is bound for, and what it does
+	fn action(&self, key: input::Key) -> Option<(usize, Action)> {
+		self.players.iter().enumerate().find_map(|(index, player)| Some((index, player.key_bindings.action(key)?)))
 	}
 
-	fn update_piece(&mut self, piece_inedx: usize, new_piece: Piece, update_ghost: bool) {
-		self.remove_piece(piece_inedx);
-		self.pieces[piece_inedx] = new_piece;
-		self.insert_piece(piece_inedx);
-		if update_ghost {
-			self.update_ghost(true);
-		}
-	}
+	// turns a key into the inputs for the engines, which go into the inputs of the player it's bound for
+	fn handle_key(&mut self, event: input::KeyEvent, inputs: &mut [Vec<GameInput>]) {
+		use input::KeyState::*;
 
-	fn update_ghost(&mut self, try_remove: bool) {
-		// Damnit, rust! so many indexing operations!!!
-		// todo Should I consider copying instead?
-		if try_remove {
-			self.remove_ghost();
-		}
-//		if try_remove && self.ghost().pos != self.faller().pos { // 2 indexings here
-//			self.remove_piece(GHOST_INDEX); // 1 indexing here
-//		}
-		*self.ghost_mut() = self.faller().clone_as_ghost(); // 2 indexings here
-		self.throw_piece(GHOST_INDEX, Direction::Down); // 1 indexing here
-		if self.ghost().pos != self.faller().pos { // 2 indexings here
-			self.insert_piece(GHOST_INDEX); // 1 indexing here
-		}
-	}
-
-	// moves the piece by the direction parameter
-	// returns false if cannot move the piece
-	fn try_move_piece(&mut self, piece: &mut Piece, d: Direction) -> bool {
-		let offset = V2::from(d);
-		let moved = piece.translated(offset);
-		for index in moved.data.indexes_of_cells_colliding(d.into()) {
-			let pos = moved.points[index];
-			if !self.bounds_contain(pos) || self.at(pos).is_full() {
-				return false;
+		// ctrl+c always quits, since raw mode keeps it from interrupting the game
+		if event.key == input::Key::new(input::KeyCode::Char('c'), input::Modifiers::CTRL) {
+			if event.state == Pressed {
+				self.end_game();
 			}
+			return;
 		}
-		*piece = moved;
-		true
-	}
-
-	fn try_move_piece_and_update(&mut self, piece_index: usize, d: Direction) -> bool {
-		let mut piece = self.pieces[piece_index].clone();
-		if self.try_move_piece(&mut piece, d) { // make the function return a moved named Piece
-			self.update_piece(piece_index, piece, !matches!(d, Direction::Down | Direction::Up));
-			return true;
+		if self.paused.is_some() {
+			self.handle_menu_key(event);
+			return;
 		}
-		false
-	}
-}
-
-impl<R: Read, W: Write> Tetris<R, W> {
-	pub fn new(width : usize, height : usize, output : W, input : R) -> Self {
-		let now = time::Instant::now();
-		let temp = Piece::new(V2::new(width as i32 / 2, 0), Cell::Full(Shape::I));
-		let temp_ghost = temp.clone_as_ghost();
-		Self {
-			width, height,
-			map: vec![Default::default(); width * height],
-			score: 0,
-			game_over: true,
-			pieces: [temp, temp_ghost],
-			now,
-			start_time: now,
-			next_frame_time: now,
-			next_gravity_time: now,
-			next_input_time: now,
-			display_changed: true,
-			output,
-			input,
+		let Some((player, action)) = self.action(event.key) else {
+			return;
+		};
+		match (action, event.state) {
+			// held moves are repeated by the engine, which is told when their keys go down and up
+			(Action::Game(action), state) if Move::from_input(action).is_some() => {
+				let direction = Move::from_input(action).unwrap();
+				let held_keys = &mut self.players[player].held_keys;
+				inputs[player].extend(match state {
+					Pressed => held_keys.press(direction, self.now),
+					Repeated => held_keys.repeat(direction, self.now),
+					Released => held_keys.release(direction),
+				});
+			}
+			(Action::Game(action), Pressed) => inputs[player].push(action),
+			(Action::Pause, Pressed) => self.pause(),
+			(Action::Quit, Pressed) => self.end_game(),
+			_ => (),
 		}
 	}
 
-	fn at_mut(&mut self, p: V2) -> &mut Cell{
-		&mut self.map[p.x as usize + p.y as usize * self.width]
-	}
-
-	fn at(&self, p: V2) -> &Cell{
-		&self.map[p.x as usize + p.y as usize * self.width]
-	}
+	// the pause menu is moved through with the up and down keys or the rotate clockwise and soft drop keys,
+	// and chosen from with enter or the hard drop key
+	fn handle_menu_key(&mut self, event: input::KeyEvent) {
+		use input::KeyCode;
 
-	fn calc_clear_rows_score(&self, rows_cleared: usize) -> u32 {
-		rows_cleared as u32 * 100 * if rows_cleared < 4 {1} else {2}
-	}
-
-	fn clear_rows_and_update_score(&mut self, points: &[V2]) {
-		let mut unique_y_values = [0; 4];
-		let mut y_count = 0u8;
-		let mut rows_cleared = 0u8;
-		for p in points {
-			if !unique_y_values[..y_count as usize].contains(&p.y) {
-				unique_y_values[y_count as usize] = p.y;
-				y_count += 1;
-				if self.check_row(p.y as usize) {
-					self.clear_rows(p.y as usize, 1);
-					rows_cleared += 1;
+		let Some(selected) = self.paused else {
+			return;
+		};
+		if event.state == input::KeyState::Released {
+			return;
+		}
+		let items = self.menu_items();
+		match (self.action(event.key).map(|(_, action)| action), event.key.code) {
+			(Some(Action::Game(GameInput::SpinLeft)), _) | (_, KeyCode::Up) => {
+				self.paused = Some((selected + items.len() - 1) % items.len());
+			}
+			(Some(Action::Game(GameInput::MoveDown)), _) | (_, KeyCode::Down) => {
+				self.paused = Some((selected + 1) % items.len());
+			}
+			(Some(Action::Game(GameInput::DropDown)), _) | (_, KeyCode::Enter) => match items[selected] {
+				MenuItem::Resume => self.resume(),
+				MenuItem::Restart => {
+					self.players.iter_mut().for_each(|player| player.engine.reset());
+					self.resume();
 				}
+				MenuItem::Quit => self.end_game(),
 			}
+			(Some(Action::Pause), _) => self.resume(),
+			(Some(Action::Quit), _) => self.end_game(),
+			_ => return,
 		}
-		self.score += self.calc_clear_rows_score(rows_cleared as usize);
+		self.display_changed = true;
 	}
 
-	fn check_t_spin(&mut self) {
-		let faller = self.faller();
-		if faller.cell_value == Cell::Full(Shape::T) {
-			let mut count = 0;
-			let p = faller.pos;
-			let offsets: [V2; 4] = [V2::new(-1, -1), V2::new(1, -1), V2::new(-1, 1), V2::new(1, 1)];
-			for offset in offsets {
-				let p = p + offset;
-				if self.bounds_contain(p) && self.at(p).is_full() {
-					count += 1;
-				}
-			}
-			if count >= 3 {
-				self.score += T_SPIN_SCORE;
-			}
+	// a replay can't be restarted, only watched on or left
+	fn menu_items(&self) -> &'static [MenuItem] {
+		if self.playback.is_some() {
+			&[MenuItem::Resume, MenuItem::Quit]
+		} else {
+			&[MenuItem::Resume, MenuItem::Restart, MenuItem::Quit]
 		}
 	}
 
-	// spawns a tetris piece at the top middle of the map
-	fn spawn_random_piece(&mut self, index_of_piece_to_clear: usize) -> Result<(), SpawningError> {
-		use SpawningError::*;
-
-		self.clear_rows_and_update_score(&self.pieces[index_of_piece_to_clear].points.clone());
-
-		let piece = Piece::new(V2::new(self.width as i32 / 2, 0), Cell::Full(rand::random())); // todo add rng
-		if piece.points.iter().any(|p| self.at(*p).is_full()) {
-			return Err(GameOver);
+	// stops the game until it's resumed. the engine only moves on with the time it's stepped by,
+	// so gravity and the lock delay pick up where they were instead of catching up on the paused time
+	fn pause(&mut self) {
+		// the players on the other end don't stop
+		if self.remote.is_some() || self.spectator.is_some() {
+			return;
 		}
-		*self.faller_mut() = piece;
-		self.update_ghost(false);
-		self.insert_piece(FALLER_INDEX);
-		self.reset_gravity_time();
-		Ok(())
+		self.paused = Some(0);
+		self.players.iter_mut().for_each(Player::release_keys);
+		self.display_changed = true;
 	}
 
-	fn reset_gravity_time(&mut self) {
-		self.next_gravity_time = self.now;
-		self.update_next_gravity_time();
+	fn resume(&mut self) {
+		self.paused = None;
+		self.display_changed = true;
 	}
 
-	fn update_next_gravity_time(&mut self) {
-		self.next_gravity_time += GAME_GRAVITY_INTERVAL;
+	// steps the engine of every player up to now, and hands the garbage each one sends to the others
+	fn step_players(&mut self, inputs: &[Vec<GameInput>], dt: time::Duration) -> Vec<GameEvent> {
+		let mut events = Vec::new();
+		for (index, inputs) in inputs.iter().enumerate() {
+			let player = &mut self.players[index];
+			let mut inputs = inputs.clone();
+			inputs.extend(player.held_keys.due(self.now));
+			let player_events = player.engine.step(&inputs, dt);
+			for event in &player_events {
+				if let Some(outcome) = event.outcome() {
+					self.ended_first.get_or_insert((index, outcome));
+				}
+				if let GameEvent::Attack { rows } = event {
+					for (_, opponent) in self.players.iter_mut().enumerate().filter(|(other, _)| *other != index) {
+						opponent.engine.receive_garbage(*rows);
+					}
+				}
+			}
+			events.extend(player_events);
+		}
+		events
 	}
 
-	fn spin(&mut self, rotate_left: bool) -> bool {
-		if matches!(self.faller().cell_value.unwrap(), Shape::O) { // todo this is not a solution!!!!
-			return false;
-		}
-		let mut rotated = if rotate_left {
-			self.faller().rotated_left()
-		} else {
-			self.faller().rotated_right()
+	// sends what happened on the board to the opponent over the network, and takes in what happened on theirs
+	fn sync_remote(&mut self, events: &[GameEvent]) {
+		let Some(remote) = &mut self.remote else {
+			return;
 		};
-
-		let mut v = V2::new(0, 0);
-		for p in rotated.points {
-			if p.x < 0 {
-				v.x += 1;
-			} else if p.x >= self.width as i32 {
-				v.x -= 1;
-			}
-			if p.y < 0 {
-				v.y += 1;
-			} else if p.y >= self.height as i32 {
-				v.y -= 1;
+		let engine = &mut self.players[0].engine;
+		let player = remote.player;
+		let mut messages = Vec::new();
+		for event in events {
+			if let Some(outcome) = event.outcome() {
+				remote.end(player, outcome, engine.score());
+				messages.push(Message::GameOver { player, outcome, score: engine.score() });
+				continue;
 			}
+			messages.push(match event {
+				GameEvent::PieceSpawned(_) | GameEvent::Locked(_) | GameEvent::LinesCleared { .. } => {
+					Message::Event { player, event: event.clone() }
+				}
+				GameEvent::Attack { rows } => Message::Garbage { rows: *rows },
+				_ => continue,
+			});
 		}
-
-		if v != V2::new(0, 0) {
-			rotated = rotated.translated(v);
+		if engine.inputs().len() > remote.inputs_sent {
+			messages.push(Message::Inputs { player, inputs: engine.inputs()[remote.inputs_sent..].to_vec() });
+			remote.inputs_sent = engine.inputs().len();
 		}
-
-		// now check if it doesn't intersect with other things
-//		let intersects: bool = rotated.points.iter().any(|p| self.at(*p).is_some() && self.piece.points.iter().find(|v| *p == **v).is_none());
-		let intersects: bool = rotated.points.iter().any(|p| self.at(*p).is_full() && !self.faller().points.iter().any(|v| *p == *v));
-
-		if intersects {
-			// todo try different positions
-		} else {
-			self.update_piece(FALLER_INDEX, rotated, true);
-//			*self.piece_mut() = rotated;
-			return true;
+		// the board is sent every time it changes, but not more often than the opponent can see it
+		remote.board_changed |= !events.is_empty();
+		if remote.board_changed && (self.now >= remote.next_board_time || engine.has_ended()) {
+			messages.push(Message::Board { player, snapshot: Box::new(engine.board_snapshot()) });
+			remote.board_changed = false;
+			remote.next_board_time = self.now + BOARD_SEND_INTERVAL;
 		}
-		false
-	}
-
-	// continuously moves a piece until it can't move anymore and return the number of moves
-	fn throw_piece(&mut self, piece_index: usize, d: Direction) -> u32 {
-		let mut moves = 0;
-		let mut moved = self.pieces[piece_index].clone();
-		while self.try_move_piece(&mut moved, d) {
-			moves += 1;
+		for message in &messages {
+			if let Err(e) = remote.connection.send(message) {
+				remote.result.get_or_insert(e);
+				self.quit = true;
+				return;
+			}
 		}
-		self.pieces[piece_index] = moved;
-		moves
-	}
 
-	fn bounds_contain(&self, V2{x,y}: V2) -> bool {
-		x < self.width as i32 && x >= 0 && y >= 0 && y < self.height as i32
-	}
-
-	fn check_row(&self, y: usize) -> bool {
-		let i = y * self.width;
-		self.map[i..i + self.width].iter().all(Cell::is_full)
-	}
-
-	// clears a number of rows and drops the rows above it
-	// should be called after check_rows() returns true
-	fn clear_rows(&mut self, y: usize, rows_down: usize) {
-		let thickness = self.width * rows_down;
-		let i = y * self.width;
-		self.map[i..i + thickness].iter_mut().for_each(|cell| cell.empty()); // clear rows
-		self.map[..i + thickness].rotate_right(thickness); // drop gravity
-	}
-
-	fn handle_user_input(&mut self, action: GameInput) -> Result<(), SpawningError>{
-		use Direction::*;
-		use GameInput::*;
-
-		self.display_changed = match action {
-			SpinLeft => {
-				if self.spin(true) {
-					self.update_ghost(true);
-					true
-				} else {
-					false
+		// nothing more comes in once the game is over
+		while !self.quit {
+			let Some(message) = remote.connection.try_recv() else {
+				break;
+			};
+			match message {
+				Ok(Message::Garbage { rows }) => engine.receive_garbage(rows),
+				Ok(Message::Board { snapshot, .. }) => {
+					if let Some(opponent) = remote_engine(*snapshot, engine.settings()) {
+						remote.opponent = Some(opponent);
+					}
 				}
-			}
-			SpinRight => {
-				if self.spin(false) {
-					self.update_ghost(true);
-					true
-				} else {
-					false
+				Ok(Message::GameOver { outcome, score, .. }) => {
+					self.quit = remote.end(1 - player, outcome, score);
 				}
-			}
-			MoveLeft => {
-				self.try_move_piece_and_update(FALLER_INDEX, Left)
-			}
-			MoveRight => {
-				self.try_move_piece_and_update(FALLER_INDEX, Right)
-			}
-			MoveDown => {
-				if self.try_move_piece_and_update(FALLER_INDEX, Down) {
-					self.score += 1;
-					self.reset_gravity_time();
-					true
-				} else {
-					false
+				Ok(Message::Error { message }) | Err(message) => {
+					remote.result.get_or_insert(message);
+					self.quit = true;
 				}
+				Ok(_) => continue,
 			}
-			DropDown => {
-				self.score += 2 * (self.ghost().pos.y - self.faller().pos.y) as u32;
-				self.update_piece(FALLER_INDEX, self.ghost().clone_as_full(), false);
-				// todo the following two lines can be put into a function as they are also being used in apply_gravity
-				self.spawn_piece_and_update_ghost()?;
-				true
-			}
-		};
-
-		Ok(())
+			self.display_changed = true;
+		}
 	}
 
-	// applies gravity and if fails because there is no piece or the piece can't fall, spawn a new one
-	fn apply_gravity(&mut self) -> Result<(), SpawningError> {
-		self.display_changed = true;
-		if !self.try_move_piece_and_update(FALLER_INDEX, Direction::Down) {
-			self.spawn_piece_and_update_ghost()?;
+	// takes in the boards of the game being watched, until it's decided
+	fn watch_remote(&mut self) {
+		let Some(spectator) = &mut self.spectator else {
+			return;
+		};
+		while !self.quit {
+			let Some(message) = spectator.connection.try_recv() else {
+				break;
+			};
+			match message {
+				Ok(Message::Board { player, snapshot }) if player < self.players.len() => {
+					let engine = &mut self.players[player].engine;
+					if let Some(board) = remote_engine(*snapshot, engine.settings()) {
+						*engine = board;
+					}
+				}
+				Ok(Message::GameOver { player, outcome, score }) if player < self.players.len() => {
+					self.quit = spectator.end(player, outcome, score);
+				}
+				Ok(Message::Error { message }) | Err(message) => {
+					spectator.result.get_or_insert(message);
+					self.quit = true;
+				}
+				Ok(_) => continue,
+			}
+			self.display_changed = true;
 		}
-		Ok(())
 	}
 
-	fn spawn_piece_and_update_ghost(&mut self)-> Result<(), SpawningError> {
-		let r = self.spawn_random_piece(FALLER_INDEX);
-		self.update_ghost(false);
-		r
+	// runs the replay's ticks that fit in dt, then stops the game once the replay is over
+	fn step_playback(&mut self, dt: time::Duration) -> Vec<GameEvent> {
+		let Some(playback) = &mut self.playback else {
+			return Vec::new();
+		};
+		let engine = &mut self.players[0].engine;
+		let mut events = Vec::new();
+		self.playback_since_tick += dt;
+		while self.playback_since_tick >= TICK && engine.ticks() < playback.ticks() {
+			self.playback_since_tick -= TICK;
+			events.extend(engine.tick(&playback.inputs_at(engine.ticks())));
+		}
+		if engine.ticks() >= playback.ticks() {
+			events.extend(engine.step(&playback.inputs_at(playback.ticks()), time::Duration::ZERO));
+			self.quit = true;
+			// draw the end of the replay before leaving
+			self.next_frame_time = self.now;
+			self.display_changed = true;
+		}
+		events
 	}
 
 	fn end_game(&mut self){
-		self.game_over = true;
+		self.quit = true;
 	}
 }
 
@@ -520,92 +574,235 @@ impl<R: Read, W: Write> Tetris<R, W> {
 //	}
 //}
 
-impl<R: Read, W:Write> Tetris<R, W> {
-	fn display(&mut self) {
-		// • ░ ▒ ▓ █ ▀ ▄ ≡ ■  ⎸ ⎹ ⼕
-		let f = &mut self.output;
-		write!(f, "{}", termion::color::LightBlack.fg_str()).unwrap();
-		for _ in 0..=self.width {
-			write!(f, "▀▄").unwrap();
+impl<R: Read, W: Write> Tetris<R, W> {
+	// lines drawn to the right of a board. the pieces are hidden while paused
+	fn side_panel(&self, engine: &Engine) -> Vec<String> {
+		let mut lines = vec![format!("{}HOLD", termion::color::LightBlack.fg_str())];
+		if self.paused.is_some() {
+			lines.extend([String::new(), String::new(), String::new()]);
+			lines.push(format!("{}NEXT", termion::color::LightBlack.fg_str()));
+			return lines;
+		}
+		match engine.held() {
+			// greyed out when it can't be swapped again yet
+			Some(shape) => lines.extend(preview(if engine.can_hold() { Cell::Full(shape) } else { Cell::Ghost(shape) })),
+			None => lines.extend([String::new(), String::new()]),
+		}
+		lines.push(String::new());
+
+		lines.push(format!("{}NEXT", termion::color::LightBlack.fg_str()));
+		for shape in engine.next_queue() {
+			lines.extend(preview(Cell::Full(shape)));
+			lines.push(String::new());
 		}
-		write!(f, "\n\r").unwrap();
+		lines
+	}
 
-		for row in self.map[..].chunks(self.width) {
-			write!(f, "{}▓", termion::color::LightBlack.fg_str()).unwrap();
+	// the lines of the pause menu drawn over the boards
+	fn menu(&self) -> Vec<String> {
+		let Some(selected) = self.paused else {
+			return Vec::new();
+		};
+		let items = self.menu_items();
+		let width = items.iter().map(|item| item.name().len()).max().unwrap_or(0);
+		let mut menu = vec!["PAUSED".to_string(), String::new()];
+		for (index, item) in items.iter().enumerate() {
+			menu.push(format!("{} {:<w$}", if index == selected { ">" } else { " " }, item.name(), w = width));
+		}
+		menu
+	}
+
+	// draws a player's board with its side panel, score and HUD, line by line
+	fn board(&self, engine: &Engine) -> Vec<String> {
+		// • ░ ▒ ▓ █ ▀ ▄ ≡ ■  ⎸ ⎹ ⼕
+		use std::fmt::Write;
+
+		let panel = self.side_panel(engine);
+		let mut panel = panel.iter().map(String::as_str).chain(std::iter::repeat(""));
+		// while paused the board is hidden behind the menu, so the game can't be studied with the time stopped
+		let menu = self.menu();
+		let menu_top = engine.height().saturating_sub(menu.len()) / 2;
+		let mut lines = Vec::new();
+
+		// the rows pieces spawn in are drawn above the board, with only what's locked or falling there
+		for row in engine.buffer().skip(engine.buffer_rows() - MIN_BUFFER_ROWS) {
+			let mut line = " ".to_string();
 			for cell in row {
-				write!(f, "{}", cell).unwrap();
+				match cell {
+					_ if cell.is_full() && self.paused.is_none() => write!(line, "{}", cell).unwrap(),
+					_ => line += "  ",
+				}
+			}
+			lines.push(line);
+		}
+		let mut line = termion::color::LightBlack.fg_str().to_string();
+		for _ in 0..=engine.width() {
+			line += "▀▄";
+		}
+		write!(line, "  {}", panel.next().unwrap()).unwrap();
+		lines.push(line);
+
+		for (y, row) in engine.rows().enumerate() {
+			let mut line = format!("{}▓", termion::color::LightBlack.fg_str());
+			if self.paused.is_none() {
+				for cell in row {
+					write!(line, "{}", cell).unwrap();
+				}
+			} else if let Some(text) = y.checked_sub(menu_top).and_then(|i| menu.get(i)) {
+				write!(line, "{}{:^w$}", termion::color::White.fg_str(), text, w = engine.width() * 2).unwrap();
+			} else {
+				for _ in row {
+					write!(line, "{}", Cell::Empty).unwrap();
+				}
+			}
+			write!(line, "{}▓  {}", termion::color::LightBlack.fg_str(), panel.next().unwrap()).unwrap();
+			lines.push(line);
+		}
+
+		let score = engine.score().to_string();
+		let dashes = "-".repeat((engine.width() * 2).saturating_sub(score.len())/2);
+		let mut line = termion::color::LightBlack.fg_str().to_string();
+		for _ in 0..=engine.width() {
+			line += "▄▀";
+		}
+		lines.push(line);
+		lines.push(format!("{l}{s}{l}", l=dashes, s=score));
+		lines.extend(engine.mode().hud(engine));
+		if self.players.len() > 1 || self.remote.is_some() {
+			lines.push(format!("Incoming garbage {}", engine.incoming_garbage()));
+		}
+		lines.push(engine.last_score_event().map(ScoreEvent::to_string).unwrap_or_default());
+		lines
+	}
+
+	// draws the boards side by side
+	fn display(&mut self) {
+		let mut boards: Vec<Vec<String>> = self.players.iter().map(|player| self.board(&player.engine)).collect();
+		if let Some(opponent) = self.remote.as_ref().and_then(|remote| remote.opponent.as_ref()) {
+			boards.push(self.board(opponent));
+		}
+		let widths: Vec<usize> = boards.iter()
+			.map(|board| board.iter().map(|line| visible_width(line)).max().unwrap_or(0))
+			.collect();
+		let height = boards.iter().map(Vec::len).max().unwrap_or(0);
+		let f = &mut self.output;
+		for y in 0..height {
+			for (index, board) in boards.iter().enumerate() {
+				let line = board.get(y).map(String::as_str).unwrap_or("");
+				write!(f, "{}", line).unwrap();
+				if index + 1 < boards.len() {
+					write!(f, "{}", " ".repeat(widths[index] - visible_width(line) + BOARD_GAP)).unwrap();
+				}
 			}
-			write!(f, "{}▓\n\r", termion::color::LightBlack.fg_str()).unwrap();
+			write!(f, "\n\r").unwrap();
+		}
+	}
+}
+
+// the columns a line takes up in the terminal, without its color codes
+fn visible_width(line: &str) -> usize {
+	let mut width = 0;
+	let mut in_escape = false;
+	for c in line.chars() {
+		match c {
+			'\x1b' => in_escape = true,
+			c if in_escape => in_escape = !c.is_ascii_alphabetic(),
+			_ => width += 1,
 		}
+	}
+	width
+}
 
-		let score = self.score.to_string();
-		let lines = "-".repeat((self.width * 2 - score.len())/2);
-		write!(f, "{}", termion::color::LightBlack.fg_str()).unwrap();
-		for _ in 0..=self.width {
-			write!(f, "▄▀").unwrap();
+// draws a cell's shape in its spawn rotation, two lines of four cells
+fn preview(cell: Cell) -> [String; 2] {
+	let offsets = *Tetromino::from(cell.unwrap()).current_offsets();
+	let mut lines = [String::new(), String::new()];
+	for (y, line) in lines.iter_mut().enumerate() {
+		for x in -1..=2 {
+			if offsets.contains(&V2::new(x, y as i32)) {
+				*line += &cell.to_string();
+			} else {
+				*line += "  ";
+			}
 		}
-		write!(f, "\n\r{l}{s}{l}\n\r", l=lines, s=score).unwrap();
 	}
+	lines
 }
 
 impl<R: Read, W: Write> Game for Tetris<R, W> {
 	fn reset(&mut self) {
-		self.game_over = true;
-		self.score = 0;
-		self.map.iter_mut().for_each(Cell::empty);
+		self.ended_first = None;
+		self.players.iter_mut().for_each(|player| player.engine.reset());
 	}
 
 	fn run(&mut self) -> bool {
-		self.game_over = false;
+		self.quit = false;
+		self.players.iter_mut().for_each(Player::release_keys);
 
-		write!(self.output, "{}", termion::cursor::Hide).unwrap();
+		write!(self.output, "{}{}", termion::cursor::Hide, input::ENABLE_KEY_RELEASES).unwrap();
 		self.output.flush().unwrap();
+		let finished = self.play();
+		// a game that's saved is left with nothing held
+		self.players.iter_mut().for_each(Player::release_keys);
+		write!(self.output, "{}", input::DISABLE_KEY_RELEASES).unwrap();
+		self.output.flush().unwrap();
+		finished
+	}
 
-		self.spawn_random_piece(FALLER_INDEX).unwrap();
-		self.update_ghost(false);
-		self.insert_piece(FALLER_INDEX);
+	fn score(&self) -> f32 {
+		self.engine().score() as f32
+	}
+}
 
-		self.now = time::Instant::now();
-		self.start_time = self.now;
-		self.next_frame_time = self.start_time;
-		self.next_gravity_time = self.start_time;
-		self.next_input_time = self.start_time;
+impl<R: Read, W: Write> Tetris<R, W> {
+	// the game loop, returns false if the game ended by topping out
+	fn play(&mut self) -> bool {
+		self.now = self.clock.now();
+		self.last_step_time = self.now;
+		self.next_frame_time = self.now;
+		self.next_input_time = self.now;
+		self.display_changed = true;
 
-		while !self.game_over {
-			self.now = time::Instant::now(); // self.start_time.elapsed();
+		while !self.quit {
+			self.now = self.clock.now();
 
-			// Get User-Input periodically
+			// Get User-Input periodically and step the engine up to now
 			if self.now >= self.next_input_time {
-				let mut buf = [0u8; 1];
-				self.input.read(&mut buf).expect("Reading input from the user must work!");
-				use GameInput::*;
-
-				let e = match buf[0] {
-					// todo add arrow-keys
-					b'w' | b'W' => self.handle_user_input(SpinLeft),
-					b'q' | b'Q' => self.handle_user_input(SpinRight),
-					b'a' | b'A' => self.handle_user_input(MoveLeft),
-					b'd' | b'D' => self.handle_user_input(MoveRight),
-					b's' | b'S' => self.handle_user_input(MoveDown),
-					b' ' => self.handle_user_input(DropDown),
-					b'x' | b'X' => {
-						self.end_game();
-						Ok(())
-					}
-					_ => Ok(())
-				};
-				if let Err(SpawningError::GameOver) = e {
-					return false;
+				let mut buf = [0u8; 32];
+				let bytes_read = self.input.read(&mut buf).expect("Reading input from the user must work!");
+				self.input_decoder.feed(&buf[..bytes_read], self.now);
+
+				let mut keys = Vec::new();
+				keys.extend(self.input_decoder.flush_stale(self.now));
+				keys.extend(std::iter::from_fn(|| self.input_decoder.next_key()));
+				let mut inputs = vec![Vec::new(); self.players.len()];
+				for key in keys {
+					self.handle_key(key, &mut inputs);
 				}
-				self.next_input_time += USER_INPUT_INTERVAL; // todo make update function
-			}
 
-			// Make the piece fall periodically
-			if self.now >= self.next_gravity_time {
-				if let Err(SpawningError::GameOver) = self.apply_gravity() {
-					return false
+				let dt = self.now - self.last_step_time;
+				self.last_step_time = self.now;
+				let events = if self.paused.is_some() {
+					Vec::new()
+				} else if self.playback.is_some() {
+					self.step_playback(dt)
+				} else if self.spectator.is_some() {
+					self.watch_remote();
+					Vec::new()
+				} else {
+					self.step_players(&inputs, dt)
+				};
+				self.sync_remote(&events);
+				// over the network, a game that ended on time goes on until the opponent's did too
+				if let Some(outcome) = events.iter().find_map(GameEvent::outcome) {
+					if self.remote.as_ref().is_none_or(|remote| remote.result.is_some()) {
+						return outcome.is_win();
+					}
 				}
-				self.update_next_gravity_time();
+				// a running clock is redrawn even when nothing happens
+				let shows_time = self.players.iter().any(|player| player.engine.mode().shows_time());
+				self.display_changed |= !events.is_empty() || (self.paused.is_none() && shows_time);
+				self.next_input_time += USER_INPUT_INTERVAL; // todo make update function
 			}
 
 			// Draw periodically
@@ -621,32 +818,147 @@ impl<R: Read, W: Write> Game for Tetris<R, W> {
 
 			// Sleep until next event // todo test this
 			use std::cmp::min;
-			let soonest = min(self.next_frame_time, min(self.next_input_time, self.next_gravity_time));
-			let actual_now = time::Instant::now();
-			std::thread::sleep(soonest - actual_now);//std::time::Duration::from_millis(125));
+			let mut soonest = self.next_input_time;
+			// the boards being watched are never stepped here, so they'd always be due
+			if self.paused.is_none() && self.spectator.is_none() {
+				for player in &self.players {
+					soonest = min(soonest, self.now + player.engine.time_until_update());
+				}
+			}
+			// a frame that's not needed yet isn't worth waking up for
+			if self.display_changed {
+				soonest = min(soonest, self.next_frame_time);
+			}
+			self.clock.sleep_until(soonest);
 		}
 		true
 	}
-
-	fn score(&self) -> f32 {
-		self.score as f32
-	}
 }
 
 impl<R, W> Tetris<R, W> {
-	// returns an iterator of all bools in the current state of the game.
-	// it's lazy so if you update the game, the values iterated will change
-	pub fn serialize(&self) -> impl Iterator<Item = bool> + '_ {
-		self.map.iter().map(Cell::is_full)
+	pub fn seed(&self) -> u64 {
+		self.engine().seed()
+	}
+
+	// the first player's engine
+	pub fn engine(&self) -> &Engine {
+		&self.players[0].engine
+	}
+
+	// how a game over the network that was played or watched ended: who won, or why it couldn't go on
+	pub fn remote_result(&self) -> Option<&str> {
+		match (&self.remote, &self.spectator) {
+			(Some(remote), _) => remote.result.as_deref(),
+			(_, Some(spectator)) => spectator.result.as_deref(),
+			_ => None,
+		}
+	}
+
+	// the player whose game ended first and how, once one has
+	pub fn ended_first(&self) -> Option<(usize, Outcome)> {
+		self.ended_first
+	}
+
+	// how a versus game on this machine came out, once it has
+	pub fn versus_result(&self) -> Option<VersusResult> {
+		let (first, outcome) = self.ended_first.filter(|_| self.players.len() > 1)?;
+		let scores = [self.players[0].engine.score(), self.players[1].engine.score()];
+		Some(VersusResult::decide(first, outcome, scores))
 	}
+
+	// the engine of every player, in the order the boards are drawn
+	pub fn engines(&self) -> impl Iterator<Item = &Engine> + '_ {
+		self.players.iter().map(|player| &player.engine)
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameInput {
+	MoveLeft, MoveRight, MoveDown, DropDown, SpinLeft, SpinRight, Spin180, Hold,
+	// a move key went down or up. it moves once when pressed, then the engine repeats it until it's released:
+	// sideways after the DAS at the ARR, soft drop at the gravity sped up by the soft drop factor
+	Press(Move), Release(Move),
 }
 
-#[derive(Debug)]
-enum SpawningError {
-	GameOver,
+// the moves that repeat while their key is held
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Move {
+	Left, Right, Down
 }
 
-#[derive(Copy, Clone)]
-enum GameInput {
-	MoveLeft, MoveRight, MoveDown, DropDown, SpinLeft, SpinRight
+impl Move {
+	pub fn from_input(input: GameInput) -> Option<Self> {
+		match input {
+			GameInput::MoveLeft => Some(Self::Left),
+			GameInput::MoveRight => Some(Self::Right),
+			GameInput::MoveDown => Some(Self::Down),
+			_ => None,
+		}
+	}
+
+	pub fn input(self) -> GameInput {
+		match self {
+			Self::Left => GameInput::MoveLeft,
+			Self::Right => GameInput::MoveRight,
+			Self::Down => GameInput::MoveDown,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use mode::GameMode;
+
+	// two players of a short Ultra, on the same seed
+	fn ultra() -> Tetris<std::io::Empty, Vec<u8>> {
+		let settings = Settings { mode: GameMode::Ultra { time: time::Duration::from_secs(1) }, ..Settings::default() };
+		let engine = || Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 3, settings.clone());
+		let randomizer = randomizer::from_name("7bag").unwrap();
+		let scoring = scoring::from_name("guideline").unwrap();
+		let mut game = Tetris::new(Vec::new(), std::io::empty(), KeyBindings::default(), randomizer, scoring, 3, settings.clone())
+			.with_opponent(engine(), KeyBindings::default());
+		game.reset();
+		game
+	}
+
+	// plays the inputs, then runs both clocks out
+	fn play_out(game: &mut Tetris<std::io::Empty, Vec<u8>>, inputs: [Vec<GameInput>; 2]) -> Option<VersusResult> {
+		game.step_players(&inputs, time::Duration::ZERO);
+		let events = game.step_players(&[Vec::new(), Vec::new()], time::Duration::from_secs(2));
+		assert_eq!(events.iter().filter_map(GameEvent::outcome).count(), 2);
+		assert!(game.engines().all(Engine::has_ended));
+		game.versus_result()
+	}
+
+	#[test]
+	fn time_up_is_won_by_the_higher_score() {
+		assert_eq!(play_out(&mut ultra(), [vec![GameInput::DropDown], vec![]]), Some(VersusResult::Winner(0)));
+		assert_eq!(play_out(&mut ultra(), [vec![], vec![GameInput::DropDown]]), Some(VersusResult::Winner(1)));
+		assert_eq!(play_out(&mut ultra(), [vec![GameInput::DropDown], vec![GameInput::DropDown]]), Some(VersusResult::Draw));
+	}
+
+	#[test]
+	fn time_up_over_the_network_waits_for_both_scores() {
+		let time_up = Outcome::Finished(engine::FinishReason::TimeUp);
+		let mut scores = [None; 2];
+		assert_eq!(VersusResult::over_network(&mut scores, 0, time_up, 100), None);
+		assert_eq!(VersusResult::over_network(&mut scores, 1, time_up, 200), Some(VersusResult::Winner(1)));
+		// topping out decides it right away
+		let top_out = Outcome::GameOver(engine::GameOverReason::TopOut);
+		assert_eq!(VersusResult::over_network(&mut [None; 2], 1, top_out, 500), Some(VersusResult::Winner(0)));
+	}
+
+	#[test]
+	fn remote_boards_of_another_size_are_dropped() {
+		let board = |settings: Settings| {
+			let mut engine = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 3, settings);
+			engine.reset();
+			engine.board_snapshot()
+		};
+		let settings = Settings::default();
+		assert!(remote_engine(board(settings.clone()), &settings).is_some());
+		assert!(remote_engine(board(Settings { width: 4, ..settings.clone() }), &settings).is_none());
+		assert!(remote_engine(board(Settings { buffer_rows: settings.buffer_rows + 3, ..settings.clone() }), &settings).is_none());
+	}
 }
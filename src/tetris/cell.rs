@@ -1,22 +1,19 @@
 use std::fmt;
+use serde::{Serialize, Deserialize};
 use super::tetromino::Shape;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Cell {
+	#[default]
 	Empty,
 	Full(Shape),
 	Ghost(Shape),
+	Garbage, // pushed up from the bottom, it has no shape of its own
 }
 
 //#[derive(Copy, Clone, Default)]
 //struct Cell(Option<Shape>);
 
-impl Default for Cell {
-	fn default() -> Self {
-		Self::Empty
-	}
-}
-
 impl Cell {
 	pub fn empty(&mut self) {
 		*self = Self::Empty;
@@ -32,7 +29,11 @@ impl Cell {
 
 	// instead of creating closures every time
 	pub fn is_full(&self) -> bool {
-		matches!(self, Self::Full(_))
+		matches!(self, Self::Full(_) | Self::Garbage)
+	}
+
+	pub fn is_garbage(&self) -> bool {
+		matches!(self, Self::Garbage)
 	}
 
 	pub fn is_ghost(&self) -> bool {
@@ -48,6 +49,7 @@ impl Cell {
 		match self {
 			Full(shape) | Ghost(shape) => *shape,
 			Empty => panic!("Can't unwrap Cell::Empty. Can only unwrap Cell::Full & Cell::Ghost"),
+			Garbage => panic!("Can't unwrap Cell::Garbage. Can only unwrap Cell::Full & Cell::Ghost"),
 		}
 	}
 }
@@ -66,6 +68,7 @@ impl fmt::Display for Cell {
 			Full(shape) => write!(f, "{}██", shape.fg_color_str()),
 			Ghost(_) => write!(f, "{}░▒", White.fg_str()),// • ░▒▓█▀▄≡■ 
 			Empty => write!(f, "{}██", Black.fg_str()),
+			Garbage => write!(f, "{}▓▓", LightBlack.fg_str()),
 		}
 	}
 }
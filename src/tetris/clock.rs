@@ -0,0 +1,52 @@
+// Where the front-end gets the time from. The wall clock plays in real time,
+// a manual clock jumps ahead instead of sleeping, so a game can run as fast as possible
+
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+	// the time since the clock started
+	fn now(&self) -> Duration;
+	// waits until now() reaches the time
+	fn sleep_until(&mut self, time: Duration);
+}
+
+pub struct SystemClock {
+	start: Instant,
+}
+
+impl Default for SystemClock {
+	fn default() -> Self {
+		Self { start: Instant::now() }
+	}
+}
+
+impl Clock for SystemClock {
+	fn now(&self) -> Duration {
+		self.start.elapsed()
+	}
+
+	fn sleep_until(&mut self, time: Duration) {
+		std::thread::sleep(time.saturating_sub(self.now()));
+	}
+}
+
+#[derive(Default)]
+pub struct ManualClock {
+	now: Duration,
+}
+
+impl ManualClock {
+	pub fn advance(&mut self, dt: Duration) {
+		self.now += dt;
+	}
+}
+
+impl Clock for ManualClock {
+	fn now(&self) -> Duration {
+		self.now
+	}
+
+	fn sleep_until(&mut self, time: Duration) {
+		self.now = self.now.max(time);
+	}
+}
@@ -0,0 +1,1573 @@
+// The rules of the game without any input, output or clock of its own: a front-end feeds it inputs
+// and the time that passed, and gets back what happened. Stats, audio or replay tools can also
+// subscribe to everything that happens, with a callback or a channel
+
+use super::v2::*;
+use super::cell::*;
+use super::direction::*;
+use super::tetromino::{Tetromino, Shape};
+use super::tetromino::offsets::KICK_TESTS;
+use super::randomizer::{self, Randomizer};
+use super::gravity::{Gravity, GravityCurve};
+use super::scoring::{self, Scoring, ScoreEvent, Lock, Spin};
+use super::settings::{Settings, LockReset, INFINITE_SOFT_DROP_FACTOR, MAX_NEXT_QUEUE_LENGTH, MAX_LOCK_RESETS, MIN_BUFFER_ROWS};
+use super::replay::Replay;
+use super::mode::{GameMode, DIG_VISIBLE_ROWS};
+use super::garbage::{self, GarbageGenerator};
+use super::{GameInput, Move};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+
+// the game advances in fixed ticks of a 60Hz frame, so the same inputs on the same ticks always play the same game
+// no matter how the time is fed to it
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// why a game was lost
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOverReason {
+	BlockOut, // a new piece spawned overlapping the stack
+	LockOut, // a piece locked entirely inside the hidden buffer rows
+	TopOut, // garbage pushed the stack above the top of the buffer
+}
+
+// how a mode with a goal or a time limit was played to its end
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinishReason {
+	GoalReached, // the mode's line goal was cleared, or its garbage dug through
+	TimeUp, // the mode's time limit ran out
+}
+
+// how a game ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+	Finished(FinishReason),
+	GameOver(GameOverReason),
+}
+
+impl Outcome {
+	// finishing the mode wins, topping out loses. in versus, the first game to end decides who won
+	pub fn is_win(&self) -> bool {
+		matches!(self, Self::Finished(_))
+	}
+
+	// a game that ended on time ends for both players at once, and the scores decide who won
+	pub fn is_time_up(&self) -> bool {
+		matches!(self, Self::Finished(FinishReason::TimeUp))
+	}
+}
+
+impl fmt::Display for Outcome {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Finished(FinishReason::GoalReached) => "Finished",
+			Self::Finished(FinishReason::TimeUp) => "Time's up",
+			Self::GameOver(GameOverReason::BlockOut) => "Game over: block out",
+			Self::GameOver(GameOverReason::LockOut) => "Game over: lock out",
+			Self::GameOver(GameOverReason::TopOut) => "Game over: top out",
+		})
+	}
+}
+
+// everything that happens in a game, in the order it happens
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameEvent {
+	PieceSpawned(Shape),
+	Moved, // the faller moved down or sideways, on its own or by an input
+	// with the index of the wall-kick that let it rotate, 0 if it didn't need one. it's in the SRS+ 180 table
+	// for a half turn and in the SRS table otherwise
+	Rotated { kick: usize, half_turn: bool },
+	Locked(Shape),
+	LinesCleared { rows: u32, kind: Spin },
+	ScoreChanged { score: u32, points: u32 }, // the new score and the points that were just added
+	LevelUp { level: u32 },
+	Hold { held: Shape }, // the shape that went into the hold slot
+	Attack { rows: u32 }, // rows of garbage sent to the opponent, after cancelling the incoming ones
+	GarbageRaised { rows: u32 }, // incoming garbage pushed the stack up
+	GameOver { reason: GameOverReason }, // the stack topped out and the game is lost
+	Finished { reason: FinishReason }, // the mode's goal or time limit was reached
+}
+
+impl GameEvent {
+	// how the game ended, if this is the event that ended it
+	pub fn outcome(&self) -> Option<Outcome> {
+		match self {
+			Self::GameOver { reason } => Some(Outcome::GameOver(*reason)),
+			Self::Finished { reason } => Some(Outcome::Finished(*reason)),
+			_ => None,
+		}
+	}
+}
+
+type Subscriber = Box<dyn FnMut(&GameEvent)>;
+
+#[derive(Clone, PartialEq, Eq)]
+struct Piece {
+	pos: V2,
+	points: [V2; 4],
+	data: Tetromino,
+	cell_value: Cell,
+}
+
+impl fmt::Debug for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+//        f.debug_struct("Piece")
+//         .field("points", &self.points)
+//         .finish()
+         write!(f, "Points: [")?;
+         for p in self.points {
+         	write!(f, "{}", p)?;
+         }
+         write!(f, "]")
+    }
+}
+
+impl Default for Piece {
+	fn default() -> Self {
+		Self {
+			pos: V2::new(0,0),
+			points: [V2::new(0,0); 4],
+			data: Tetromino::from(Shape::I),
+			cell_value: Cell::Ghost(Shape::I),
+		}
+	}
+}
+
+impl Piece {
+	pub fn new(pos: V2, cell_value: Cell) -> Self {
+		let data = Tetromino::from(cell_value.unwrap());
+		let offsets = data.current_offsets();
+		Self {
+			pos,
+			points: [pos + offsets[0], pos + offsets[1], pos + offsets[2], pos + offsets[3]],
+			data,
+			cell_value,
+		}
+	}
+
+	pub fn clone_as_ghost(&self) -> Self {
+		let mut copy = self.clone();
+		copy.cell_value.to_ghost();
+		copy
+	}
+
+	pub fn clone_as_full(&self) -> Self {
+		let mut copy = self.clone();
+		copy.cell_value.to_full();
+		copy
+	}
+
+	pub fn translate(&mut self, v: V2) {
+		self.pos = self.pos + v;
+		self.points.iter_mut().for_each(|p| *p = *p + v);
+	}
+
+	// moves the coordinates of the piece by adding a V2 to each
+	pub fn translated(&self, v: V2) -> Self {
+		let mut copy = self.clone();
+		copy.translate(v);
+		copy
+	}
+
+	fn rotated(&self, rotate: fn(&mut Tetromino)) -> Self {
+		let mut data = self.data;
+		rotate(&mut data);
+		let pos = self.pos;
+		let offsets = data.current_offsets();
+		Self {
+			pos,
+			points: [pos + offsets[0], pos + offsets[1], pos + offsets[2], pos + offsets[3]],
+			data,
+			cell_value: self.cell_value,
+		}
+	}
+
+	pub fn rotated_left(&self) -> Self {
+		self.rotated(Tetromino::rotate_left)
+	}
+
+	pub fn rotated_right(&self) -> Self {
+		self.rotated(Tetromino::rotate_right)
+	}
+
+	pub fn rotated_180(&self) -> Self {
+		self.rotated(Tetromino::rotate_180)
+	}
+}
+
+// how the faller was last rotated, for telling T-spins apart
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Rotation {
+	Quarter(usize), // with the index of the SRS kick it took
+	Half(usize), // with the index of the 180 kick it took
+}
+
+// a move key that's held down, and when the engine repeats its move next
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+struct HeldMove {
+	direction: Move,
+	next_repeat: Duration,
+}
+
+// the faller as it's saved. its cells on the map and its ghost follow from it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SavedPiece {
+	shape: Shape,
+	rotation: i8,
+	pos: V2,
+}
+
+// the whole state of a game in progress, to save it and pick it up again later
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+	settings: Settings,
+	seed: u64,
+	randomizer: String,
+	randomizer_state: serde_json::Value,
+	scoring: String,
+	scoring_state: serde_json::Value,
+	rng: ChaCha8Rng,
+	map: Vec<Cell>, // with the faller but without its ghost
+	faller: SavedPiece,
+	score: u32,
+	last_score_event: Option<ScoreEvent>,
+	lines: u32,
+	level: u32,
+	outcome: Option<Outcome>,
+	next_queue: VecDeque<Shape>,
+	held: Option<Shape>,
+	can_hold: bool,
+	garbage: GarbageGenerator,
+	garbage_rows: u32,
+	garbage_pending: u32,
+	incoming: VecDeque<u32>,
+	lock_time: Option<Duration>,
+	lock_resets: u32,
+	lowest_y: i32,
+	last_rotation: Option<Rotation>,
+	ticks: u64,
+	now: Duration,
+	since_tick: Duration,
+	next_gravity_time: Duration,
+	held_moves: Vec<HeldMove>,
+	inputs: Vec<(u64, GameInput)>, // kept so the replay of a resumed game plays from the start
+}
+
+impl Snapshot {
+	// the settings the game was started with, to check a snapshot from elsewhere before picking it up
+	pub fn settings(&self) -> &Settings {
+		&self.settings
+	}
+}
+
+const FALLER_INDEX: usize = 0;
+const GHOST_INDEX: usize = 1;
+
+pub struct Engine {
+	width: usize, // todo can use u8
+	height: usize, // todo can use u8. the whole map, buffer rows included
+	buffer_rows: usize, // the hidden rows at the top of the map, which pieces spawn in
+	map: Vec<Cell>,
+	score: u32,
+	scoring: Box<dyn Scoring>,
+	last_score_event: Option<ScoreEvent>, // the last lock that scored points
+	lines: u32,
+	level: u32,
+	start_level: u32,
+	lines_per_level: u32,
+	gravity_curve: GravityCurve,
+	soft_drop_factor: u32,
+	das: Duration, // how long a sideways move is held before it repeats
+	arr: Duration, // the time between repeated sideways moves, 0 moves as far as the piece can go
+	held_moves: Vec<HeldMove>, // the last sideways move pressed and soft drop, while their keys are held
+	mode: GameMode,
+	garbage: GarbageGenerator,
+	garbage_rows: u32, // rows of garbage left to clear in dig mode, on the board or still to come up
+	garbage_pending: u32, // the ones still to come up
+	incoming: VecDeque<u32>, // attacks received from the opponent, in rows, that haven't come up yet
+	ended: bool, // nothing happens anymore, until the game is reset. set before the first reset too
+	outcome: Option<Outcome>, // how the game ended, once it has
+	randomizer: Box<dyn Randomizer>,
+	seed: u64,
+	rng: ChaCha8Rng, // reseeded on every reset, so the same seed and inputs play the same game
+	next_queue: VecDeque<Shape>,
+	next_queue_length: usize,
+	held: Option<Shape>,
+	can_hold: bool, // holding is allowed once per piece
+	lock_delay: Duration,
+	lock_reset: LockReset,
+	lock_time: Option<Duration>, // when the faller locks, if it's resting on the stack
+	lock_resets: u32, // times the lock delay was reset since the faller reached its lowest row
+	lowest_y: i32, // the lowest row the faller has reached
+	last_rotation: Option<Rotation>, // the last rotation, if nothing moved the faller since
+
+	pieces: [Piece; 2], // unfortunately I have to get to pieces via index instead of reference. Damn you rust
+
+	// times are counted from the start of the game, and only move a tick at a time
+	ticks: u64,
+	now: Duration,
+	since_tick: Duration, // time stepped since the last tick, that doesn't add up to a whole tick yet
+	next_gravity_time: Duration,
+	events: Vec<GameEvent>, // what happened since the last step returned
+	subscribers: Vec<Subscriber>,
+	settings: Settings, // kept for replays
+	inputs: Vec<(u64, GameInput)>, // every input since the game started, with the tick it was applied on
+}
+
+
+// self.piece functionality
+impl Engine {
+	fn faller(&self) -> &Piece {
+		&self.pieces[FALLER_INDEX]
+	}
+
+	fn ghost(&self) -> &Piece {
+		&self.pieces[GHOST_INDEX]
+	}
+
+	fn faller_mut(&mut self) -> &mut Piece {
+		&mut self.pieces[FALLER_INDEX]
+	}
+
+	fn ghost_mut(&mut self) -> &mut Piece {
+		&mut self.pieces[GHOST_INDEX]
+	}
+
+	fn fill_piece_cells_with(&mut self, piece_inedx: usize, value: Cell) {
+		for point in self.pieces[piece_inedx].points {
+			*self.at_mut(point) = value;
+		}
+	}
+
+	fn remove_piece(&mut self, piece_inedx: usize) {
+		self.fill_piece_cells_with(piece_inedx, Cell::Empty);
+	}
+
+	fn insert_piece(&mut self, piece_inedx: usize) {
+		self.fill_piece_cells_with(piece_inedx, self.pieces[piece_inedx].cell_value);
+	}
+
+	fn remove_ghost(&mut self) {
+		let g = &self.ghost();
+		if g.pos != self.faller().pos {
+			for p in g.points {
+				let c = self.at_mut(p);
+				if let Cell::Ghost(_) = *c {
+					*c = Cell::Empty;
+				}
+			}
+		}
+	}
+
+	fn update_piece(&mut self, piece_inedx: usize, new_piece: Piece, update_ghost: bool) {
+		self.remove_piece(piece_inedx);
+		self.pieces[piece_inedx] = new_piece;
+		self.insert_piece(piece_inedx);
+		if update_ghost {
+			self.update_ghost(true);
+		}
+	}
+
+	fn update_ghost(&mut self, try_remove: bool) {
+		// Damnit, rust! so many indexing operations!!!
+		// todo Should I consider copying instead?
+		if try_remove {
+			self.remove_ghost();
+		}
+//		if try_remove && self.ghost().pos != self.faller().pos { // 2 indexings here
+//			self.remove_piece(GHOST_INDEX); // 1 indexing here
+//		}
+		*self.ghost_mut() = self.faller().clone_as_ghost(); // 2 indexings here
+		self.throw_piece(GHOST_INDEX, Direction::Down); // 1 indexing here
+		if self.ghost().pos != self.faller().pos { // 2 indexings here
+			self.insert_piece(GHOST_INDEX); // 1 indexing here
+		}
+	}
+
+	// moves the piece by the direction parameter
+	// returns false if cannot move the piece
+	fn try_move_piece(&mut self, piece: &mut Piece, d: Direction) -> bool {
+		let offset = V2::from(d);
+		let moved = piece.translated(offset);
+		for index in moved.data.indexes_of_cells_colliding(d.into()) {
+			let pos = moved.points[index];
+			if !self.bounds_contain(pos) || self.at(pos).is_full() {
+				return false;
+			}
+		}
+		*piece = moved;
+		true
+	}
+
+	fn try_move_piece_and_update(&mut self, piece_index: usize, d: Direction) -> bool {
+		let mut piece = self.pieces[piece_index].clone();
+		if self.try_move_piece(&mut piece, d) { // make the function return a moved named Piece
+			self.update_piece(piece_index, piece, !matches!(d, Direction::Down | Direction::Up));
+			if piece_index == FALLER_INDEX {
+				self.emit(GameEvent::Moved);
+			}
+			return true;
+		}
+		false
+	}
+}
+
+impl Engine {
+	pub fn new(randomizer: Box<dyn Randomizer>, scoring: Box<dyn Scoring>, seed: u64, settings: Settings) -> Self {
+		let buffer_rows = settings.buffer_rows.max(MIN_BUFFER_ROWS);
+		let (width, height) = (settings.width, settings.height + buffer_rows);
+		let temp = Piece::new(V2::new(width as i32 / 2, 0), Cell::Full(Shape::I));
+		let temp_ghost = temp.clone_as_ghost();
+		Self {
+			width, height, buffer_rows,
+			map: vec![Default::default(); width * height],
+			score: 0,
+			scoring,
+			last_score_event: None,
+			lines: 0,
+			level: settings.start_level,
+			start_level: settings.start_level,
+			lines_per_level: settings.lines_per_level.max(1),
+			gravity_curve: settings.gravity.clone(),
+			soft_drop_factor: settings.soft_drop_factor.max(1),
+			das: settings.das,
+			arr: settings.arr,
+			held_moves: Vec::new(),
+			mode: settings.mode.clone(),
+			garbage: GarbageGenerator::new(settings.mode.messiness(), seed),
+			garbage_rows: 0,
+			garbage_pending: 0,
+			incoming: VecDeque::new(),
+			ended: true,
+			outcome: None,
+			randomizer,
+			seed,
+			rng: ChaCha8Rng::seed_from_u64(seed),
+			next_queue: VecDeque::with_capacity(MAX_NEXT_QUEUE_LENGTH),
+			next_queue_length: settings.next_queue_length.clamp(1, MAX_NEXT_QUEUE_LENGTH),
+			held: None,
+			can_hold: true,
+			lock_delay: settings.lock_delay,
+			lock_reset: settings.lock_reset,
+			lock_time: None,
+			lock_resets: 0,
+			lowest_y: 0,
+			last_rotation: None,
+			pieces: [temp, temp_ghost],
+			ticks: 0,
+			now: Duration::ZERO,
+			since_tick: Duration::ZERO,
+			next_gravity_time: Duration::ZERO,
+			events: Vec::new(),
+			subscribers: Vec::new(),
+			settings,
+			inputs: Vec::new(),
+		}
+	}
+
+	// starts a new game with the same seed and settings
+	pub fn reset(&mut self) {
+		self.score = 0;
+		self.scoring.reset();
+		self.last_score_event = None;
+		self.lines = 0;
+		self.level = self.start_level;
+		self.map.iter_mut().for_each(Cell::empty);
+		self.rng = ChaCha8Rng::seed_from_u64(self.seed);
+		self.garbage = GarbageGenerator::new(self.mode.messiness(), self.seed);
+		self.garbage_rows = self.mode.dig_rows().unwrap_or(0);
+		self.garbage_pending = self.garbage_rows;
+		self.incoming.clear();
+		self.randomizer.reset();
+		self.next_queue.clear();
+		self.fill_next_queue();
+		self.held = None;
+		self.can_hold = true;
+		self.held_moves.clear();
+		self.ticks = 0;
+		self.now = Duration::ZERO;
+		self.since_tick = Duration::ZERO;
+		self.events.clear();
+		self.inputs.clear();
+
+		let shape = self.pop_next_shape();
+		self.outcome = match self.raise_dig_garbage().and_then(|()| self.spawn_piece(shape)) {
+			Ok(()) => None,
+			Err(SpawningError::Ended(outcome)) => Some(outcome),
+		};
+		self.ended = self.outcome.is_some();
+	}
+
+	// applies the inputs in order, then runs a tick for every whole tick of time that passed with dt.
+	// the rest of dt carries over to the next step. returns everything that happened
+	pub fn step(&mut self, inputs: &[GameInput], dt: Duration) -> Vec<GameEvent> {
+		self.since_tick = self.since_tick.saturating_add(dt);
+		let nanos = self.since_tick.as_nanos();
+		let ticks = u64::try_from(nanos / TICK.as_nanos()).unwrap_or(u64::MAX);
+		self.since_tick = Duration::from_nanos((nanos % TICK.as_nanos()) as u64);
+		self.run(inputs, ticks)
+	}
+
+	// applies the inputs in order, then runs exactly one tick
+	pub fn tick(&mut self, inputs: &[GameInput]) -> Vec<GameEvent> {
+		self.run(inputs, 1)
+	}
+
+	fn run(&mut self, inputs: &[GameInput], ticks: u64) -> Vec<GameEvent> {
+		if !self.ended {
+			if let Err(SpawningError::Ended(outcome)) = self.update(inputs, ticks) {
+				self.ended = true;
+				self.outcome = Some(outcome);
+				self.emit(match outcome {
+					Outcome::GameOver(reason) => GameEvent::GameOver { reason },
+					Outcome::Finished(reason) => GameEvent::Finished { reason },
+				});
+			}
+		}
+		std::mem::take(&mut self.events)
+	}
+
+	// lets go of every held move, as if their keys went up. a game is left like this,
+	// since nothing releases the keys when it's picked up again
+	pub fn release_moves(&mut self) {
+		let releases: Vec<GameInput> = self.held_moves.iter().map(|held| GameInput::Release(held.direction)).collect();
+		self.step(&releases, Duration::ZERO);
+	}
+
+	// calls the callback with every event, as it happens
+	pub fn subscribe(&mut self, callback: impl FnMut(&GameEvent) + 'static) {
+		self.subscribers.push(Box::new(callback));
+	}
+
+	// sends every event to the returned receiver. dropping the receiver is fine, the events just go nowhere
+	pub fn subscribe_channel(&mut self) -> mpsc::Receiver<GameEvent> {
+		let (sender, receiver) = mpsc::channel();
+		self.subscribe(move |event| {
+			let _ = sender.send(event.clone());
+		});
+		receiver
+	}
+
+	fn emit(&mut self, event: GameEvent) {
+		for subscriber in &mut self.subscribers {
+			subscriber(&event);
+		}
+		self.events.push(event);
+	}
+
+	fn add_score(&mut self, points: u32) {
+		if points > 0 {
+			self.score += points;
+			self.emit(GameEvent::ScoreChanged { score: self.score, points });
+		}
+	}
+
+	fn update(&mut self, inputs: &[GameInput], ticks: u64) -> Result<(), SpawningError> {
+		for input in inputs {
+			self.inputs.push((self.ticks, *input));
+			self.handle_input(*input)?;
+		}
+		for _ in 0..ticks {
+			self.ticks += 1;
+			self.now += TICK;
+			if self.mode.time_limit().is_some_and(|limit| self.now >= limit) {
+				return Err(SpawningError::Ended(Outcome::Finished(FinishReason::TimeUp)));
+			}
+			self.apply_held_moves()?;
+			// Make the piece fall once for every gravity interval that passed
+			while self.now >= self.next_gravity_time {
+				self.apply_gravity();
+				self.update_next_gravity_time();
+			}
+			// Lock the piece once it rested on the stack long enough
+			self.apply_lock_delay()?;
+		}
+		Ok(())
+	}
+
+	// how long until gravity or the lock delay needs a step, even without inputs.
+	// they only happen on ticks, so this is the time until the tick they happen on
+	pub fn time_until_update(&self) -> Duration {
+		let soonest = self.held_moves.iter()
+			.map(|held| held.next_repeat)
+			.chain(self.lock_time)
+			.fold(self.next_gravity_time, Duration::min);
+		let ticks = soonest.saturating_sub(self.now).as_nanos().div_ceil(TICK.as_nanos()).max(1);
+		let until_tick = u64::try_from(ticks.saturating_mul(TICK.as_nanos())).unwrap_or(u64::MAX);
+		Duration::from_nanos(until_tick).saturating_sub(self.since_tick)
+	}
+
+	// ticks run since the game started
+	pub fn ticks(&self) -> u64 {
+		self.ticks
+	}
+
+	// everything it takes to play this game again, up to now
+	pub fn replay(&self) -> Replay {
+		Replay::new(self.seed, self.randomizer.name(), self.scoring.name(), self.settings.clone(), &self.inputs, self.ticks)
+	}
+
+	// every input since the game started, with the tick it was applied on
+	pub fn inputs(&self) -> &[(u64, GameInput)] {
+		&self.inputs
+	}
+
+	// the settings the game was started with
+	pub fn settings(&self) -> &Settings {
+		&self.settings
+	}
+
+	pub fn snapshot(&self) -> Snapshot {
+		let faller = self.faller();
+		Snapshot {
+			settings: self.settings.clone(),
+			seed: self.seed,
+			randomizer: self.randomizer.name(),
+			randomizer_state: self.randomizer.save(),
+			scoring: self.scoring.name(),
+			scoring_state: self.scoring.save(),
+			rng: self.rng.clone(),
+			map: self.map.iter().map(|cell| if cell.is_ghost() { Cell::Empty } else { *cell }).collect(),
+			faller: SavedPiece { shape: faller.cell_value.unwrap(), rotation: faller.data.rotation(), pos: faller.pos },
+			score: self.score,
+			last_score_event: self.last_score_event.clone(),
+			lines: self.lines,
+			level: self.level,
+			outcome: self.outcome,
+			next_queue: self.next_queue.clone(),
+			held: self.held,
+			can_hold: self.can_hold,
+			garbage: self.garbage.clone(),
+			garbage_rows: self.garbage_rows,
+			garbage_pending: self.garbage_pending,
+			incoming: self.incoming.clone(),
+			lock_time: self.lock_time,
+			lock_resets: self.lock_resets,
+			lowest_y: self.lowest_y,
+			last_rotation: self.last_rotation,
+			ticks: self.ticks,
+			now: self.now,
+			since_tick: self.since_tick,
+			next_gravity_time: self.next_gravity_time,
+			held_moves: self.held_moves.clone(),
+			inputs: self.inputs.clone(),
+		}
+	}
+
+	// the snapshot without the inputs, which is all it takes to draw the board somewhere else
+	pub fn board_snapshot(&self) -> Snapshot {
+		let mut snapshot = self.snapshot();
+		snapshot.inputs.clear();
+		snapshot
+	}
+
+	// picks up a game where its snapshot was taken
+	pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, String> {
+		let mut randomizer = randomizer::from_name(&snapshot.randomizer)
+			.ok_or_else(|| format!("unknown randomizer '{}'", snapshot.randomizer))?;
+		randomizer.restore(snapshot.randomizer_state)
+			.map_err(|e| format!("the randomizer can't be restored: {}", e))?;
+		let mut scoring = scoring::from_name(&snapshot.scoring)
+			.ok_or_else(|| format!("unknown scoring '{}'", snapshot.scoring))?;
+		scoring.restore(snapshot.scoring_state)
+			.map_err(|e| format!("the scoring can't be restored: {}", e))?;
+
+		let mut engine = Self::new(randomizer, scoring, snapshot.seed, snapshot.settings);
+		if snapshot.map.len() != engine.width * engine.height {
+			return Err(format!("the board has {} cells instead of {}x{}", snapshot.map.len(), engine.width, engine.height));
+		}
+		let mut data = Tetromino::from(snapshot.faller.shape);
+		data.set_rotation(snapshot.faller.rotation);
+		let offsets = data.current_offsets();
+		let pos = snapshot.faller.pos;
+		let faller = Piece {
+			pos,
+			points: [pos + offsets[0], pos + offsets[1], pos + offsets[2], pos + offsets[3]],
+			data,
+			cell_value: Cell::Full(snapshot.faller.shape),
+		};
+		if !faller.points.iter().all(|p| engine.bounds_contain(*p)) {
+			return Err("the falling piece is outside the board".to_string());
+		}
+
+		engine.map = snapshot.map;
+		engine.rng = snapshot.rng;
+		engine.score = snapshot.score;
+		engine.last_score_event = snapshot.last_score_event;
+		engine.lines = snapshot.lines;
+		engine.level = snapshot.level;
+		engine.ended = snapshot.outcome.is_some();
+		engine.outcome = snapshot.outcome;
+		engine.next_queue = snapshot.next_queue;
+		engine.held = snapshot.held;
+		engine.can_hold = snapshot.can_hold;
+		engine.garbage = snapshot.garbage;
+		engine.garbage_rows = snapshot.garbage_rows;
+		engine.garbage_pending = snapshot.garbage_pending;
+		engine.incoming = snapshot.incoming;
+		engine.lock_time = snapshot.lock_time;
+		engine.lock_resets = snapshot.lock_resets;
+		engine.lowest_y = snapshot.lowest_y;
+		engine.last_rotation = snapshot.last_rotation;
+		engine.ticks = snapshot.ticks;
+		engine.now = snapshot.now;
+		engine.since_tick = snapshot.since_tick;
+		engine.next_gravity_time = snapshot.next_gravity_time;
+		engine.held_moves = snapshot.held_moves;
+		engine.inputs = snapshot.inputs;
+		*engine.faller_mut() = faller;
+		engine.insert_piece(FALLER_INDEX);
+		engine.update_ghost(false);
+		Ok(engine)
+	}
+
+	fn at_mut(&mut self, p: V2) -> &mut Cell{
+		&mut self.map[p.x as usize + p.y as usize * self.width]
+	}
+
+	fn at(&self, p: V2) -> &Cell{
+		&self.map[p.x as usize + p.y as usize * self.width]
+	}
+
+	// returns the rows cleared
+	fn clear_rows_and_update_score(&mut self, points: &[V2]) -> u32 {
+		let spin = self.check_t_spin();
+
+		let mut unique_y_values = [0; 4];
+		let mut y_count = 0u8;
+		let mut rows_cleared = 0u8;
+		for p in points {
+			if !unique_y_values[..y_count as usize].contains(&p.y) {
+				unique_y_values[y_count as usize] = p.y;
+				y_count += 1;
+				if self.check_row(p.y as usize) {
+					if self.row_has_garbage(p.y as usize) {
+						self.garbage_rows = self.garbage_rows.saturating_sub(1);
+					}
+					self.clear_rows(p.y as usize, 1);
+					rows_cleared += 1;
+				}
+			}
+		}
+
+		let event = self.scoring.lock(Lock {
+			rows: rows_cleared as u32,
+			spin,
+			perfect_clear: !self.map.iter().any(Cell::is_full),
+			level: self.level,
+		});
+		if rows_cleared > 0 {
+			self.emit(GameEvent::LinesCleared { rows: rows_cleared as u32, kind: spin });
+			self.send_attack(garbage::attack(&event));
+		}
+		self.add_score(event.total);
+		if event.total > 0 {
+			self.last_score_event = Some(event);
+		}
+		self.lines += rows_cleared as u32;
+		let level = self.start_level + self.lines / self.lines_per_level;
+		if level != self.level {
+			self.level = level;
+			self.emit(GameEvent::LevelUp { level });
+		}
+		rows_cleared as u32
+	}
+
+	// cancels the incoming garbage with an attack, oldest first, and sends what's left of it
+	fn send_attack(&mut self, mut rows: u32) {
+		while rows > 0 {
+			let Some(incoming) = self.incoming.front_mut() else {
+				break;
+			};
+			let cancelled = rows.min(*incoming);
+			rows -= cancelled;
+			*incoming -= cancelled;
+			if *incoming == 0 {
+				self.incoming.pop_front();
+			}
+		}
+		if rows > 0 {
+			self.emit(GameEvent::Attack { rows });
+		}
+	}
+
+	// pushes up all the incoming garbage, with the rows of each attack sharing a hole
+	fn raise_incoming_garbage(&mut self) -> Result<(), SpawningError> {
+		while let Some(rows) = self.incoming.pop_front() {
+			let hole = self.garbage.next_hole(self.width);
+			self.add_garbage(&vec![hole; rows as usize])?;
+			self.emit(GameEvent::GarbageRaised { rows });
+		}
+		Ok(())
+	}
+
+	// a T that was rotated into place, with at least 3 of the 4 corners around its center taken by cells or walls.
+	// it's a full T-spin when both corners on the side it points to are taken, or when it got there with the
+	// last SRS kick, otherwise it's a mini
+	fn check_t_spin(&self) -> Spin {
+		let faller = self.faller();
+		let rotation = match self.last_rotation {
+			Some(rotation) if faller.cell_value == Cell::Full(Shape::T) => rotation,
+			_ => return Spin::None,
+		};
+		let p = faller.pos;
+		// the offsets of a T add up to the cell it points to
+		let front = faller.points.iter().fold(V2::new(0, 0), |sum, point| sum + (*point - p));
+		let side = V2::new(front.y, front.x);
+		let taken = |corner: &V2| !self.bounds_contain(p + *corner) || self.at(p + *corner).is_full();
+
+		let front_corners = [front + side, front - side].iter().filter(|c| taken(c)).count();
+		let back_corners = [front * -1 + side, front * -1 - side].iter().filter(|c| taken(c)).count();
+		if front_corners + back_corners < 3 {
+			Spin::None
+		} else if front_corners == 2 || rotation == Rotation::Quarter(KICK_TESTS - 1) {
+			Spin::Full
+		} else {
+			Spin::Mini
+		}
+	}
+
+	// tops up the next queue with pieces from the randomizer
+	fn fill_next_queue(&mut self) {
+		while self.next_queue.len() < self.next_queue_length {
+			let shape = self.randomizer.next_shape(&mut self.rng);
+			self.next_queue.push_back(shape);
+		}
+	}
+
+	// takes the first piece out of the next queue
+	fn pop_next_shape(&mut self) -> Shape {
+		self.fill_next_queue();
+		let shape = self.next_queue.pop_front().unwrap();
+		self.fill_next_queue();
+		shape
+	}
+
+	// spawns the next tetris piece at the top middle of the map
+	fn spawn_random_piece(&mut self, index_of_piece_to_clear: usize) -> Result<(), SpawningError> {
+		let locked = &self.pieces[index_of_piece_to_clear];
+		let lock_out = locked.points.iter().all(|p| (p.y as usize) < self.buffer_rows);
+		self.emit(GameEvent::Locked(locked.cell_value.unwrap()));
+		if lock_out && self.mode.can_top_out() {
+			return Err(SpawningError::Ended(Outcome::GameOver(GameOverReason::LockOut)));
+		}
+		let rows_cleared = self.clear_rows_and_update_score(&self.pieces[index_of_piece_to_clear].points.clone());
+		// garbage waits while the player keeps clearing rows
+		if rows_cleared == 0 {
+			self.raise_incoming_garbage()?;
+		}
+		if self.mode.line_goal().is_some_and(|goal| self.lines >= goal) || (self.mode.dig_rows().is_some() && self.garbage_rows == 0) {
+			return Err(SpawningError::Ended(Outcome::Finished(FinishReason::GoalReached)));
+		}
+		self.raise_dig_garbage()?;
+
+		let shape = self.pop_next_shape();
+		self.can_hold = true;
+		self.spawn_piece(shape)
+	}
+
+	// spawns a tetris piece of the given shape in the middle of the lowest two buffer rows, in its spawn rotation
+	fn spawn_piece(&mut self, shape: Shape) -> Result<(), SpawningError> {
+		let spawn_y = (self.buffer_rows - MIN_BUFFER_ROWS) as i32;
+		let piece = Piece::new(V2::new(self.width as i32 / 2, spawn_y), Cell::Full(shape));
+		// without game over, the bottom rows make way for the new piece
+		while !self.mode.can_top_out() && piece.points.iter().any(|p| self.at(*p).is_full()) {
+			self.clear_rows(self.height - 1, 1);
+		}
+		if piece.points.iter().any(|p| self.at(*p).is_full()) {
+			return Err(SpawningError::Ended(Outcome::GameOver(GameOverReason::BlockOut)));
+		}
+		self.emit(GameEvent::PieceSpawned(shape));
+		*self.faller_mut() = piece;
+		self.update_ghost(false);
+		self.insert_piece(FALLER_INDEX);
+		self.reset_gravity_time();
+		self.lock_time = None;
+		self.lock_resets = 0;
+		self.lowest_y = self.faller().pos.y;
+		self.last_rotation = None;
+		self.apply_instant_gravity();
+		Ok(())
+	}
+
+	// the faller can't fall any further
+	fn is_grounded(&self) -> bool {
+		self.ghost().pos == self.faller().pos
+	}
+
+	// starts, restarts or stops the lock delay after the faller moved, according to the lock reset mode
+	fn update_lock_time(&mut self) {
+		let y = self.faller().pos.y;
+		if y > self.lowest_y {
+			self.lowest_y = y;
+			if self.lock_reset != LockReset::None {
+				self.lock_time = None;
+				self.lock_resets = 0;
+			}
+		} else if self.lock_reset == LockReset::Move && self.lock_time.is_some() && self.lock_resets < MAX_LOCK_RESETS {
+			self.lock_time = None;
+			self.lock_resets += 1;
+		}
+
+		if !self.is_grounded() {
+			if self.lock_reset != LockReset::None {
+				self.lock_time = None;
+			}
+		} else if self.lock_time.is_none() {
+			self.lock_time = Some(self.now + self.lock_delay);
+		}
+	}
+
+	// locks the faller in place if it has been resting on the stack for the whole lock delay
+	fn apply_lock_delay(&mut self) -> Result<(), SpawningError> {
+		if matches!(self.lock_time, Some(lock_time) if self.now >= lock_time) && self.is_grounded() {
+			self.spawn_piece_and_update_ghost()?;
+		}
+		Ok(())
+	}
+
+	// puts the faller in the hold slot and takes out the piece that was held, or the next piece if none was
+	// returns false if already held since the last piece spawned
+	fn hold(&mut self) -> Result<bool, SpawningError> {
+		if !self.can_hold {
+			return Ok(false);
+		}
+		let shape = self.faller().cell_value.unwrap();
+		self.remove_ghost();
+		self.remove_piece(FALLER_INDEX);
+		let next = match self.held.replace(shape) {
+			Some(held) => held,
+			None => self.pop_next_shape(),
+		};
+		self.can_hold = false;
+		self.emit(GameEvent::Hold { held: shape });
+		self.spawn_piece(next)?;
+		Ok(true)
+	}
+
+	fn reset_gravity_time(&mut self) {
+		self.next_gravity_time = self.now;
+		self.update_next_gravity_time();
+	}
+
+	fn gravity(&self) -> Gravity {
+		self.gravity_curve.gravity(self.level)
+	}
+
+	fn update_next_gravity_time(&mut self) {
+		self.next_gravity_time += match self.gravity() {
+			Gravity::Interval(interval) => interval,
+			Gravity::Instant => TICK,
+		};
+	}
+
+	// how often a held soft drop moves the faller down. at most once a tick, unless it goes straight down
+	pub fn soft_drop_interval(&self) -> Duration {
+		match self.gravity() {
+			Gravity::Interval(_) if self.soft_drop_factor == INFINITE_SOFT_DROP_FACTOR => Duration::ZERO,
+			Gravity::Interval(interval) => (interval / self.soft_drop_factor).max(TICK),
+			Gravity::Instant => Duration::ZERO,
+		}
+	}
+
+	// with 20G, drops the faller to the bottom right away
+	fn apply_instant_gravity(&mut self) {
+		if self.gravity() == Gravity::Instant && !self.is_grounded() {
+			self.update_piece(FALLER_INDEX, self.ghost().clone_as_full(), false);
+			self.last_rotation = None;
+			self.emit(GameEvent::Moved);
+		}
+	}
+
+	// rotates the faller, trying each of the wall-kicks of its shape in order: SRS for quarter turns
+	// and SRS+ for half turns. returns how it rotated, or None if the piece can't rotate
+	fn spin(&mut self, action: GameInput) -> Option<Rotation> {
+		let faller = self.faller();
+		let (rotated, kicks) = match action {
+			GameInput::SpinLeft => {
+				let rotated = faller.rotated_left();
+				let kicks = faller.data.kicks_to(&rotated.data).to_vec();
+				(rotated, kicks)
+			}
+			GameInput::SpinRight => {
+				let rotated = faller.rotated_right();
+				let kicks = faller.data.kicks_to(&rotated.data).to_vec();
+				(rotated, kicks)
+			}
+			GameInput::Spin180 => (faller.rotated_180(), faller.data.kicks_180().to_vec()),
+			_ => return None,
+		};
+
+		for (index, kick) in kicks.into_iter().enumerate() {
+			let kicked = rotated.translated(kick);
+			if self.fits_faller(&kicked) {
+				self.update_piece(FALLER_INDEX, kicked, true);
+				return Some(match action {
+					GameInput::Spin180 => Rotation::Half(index),
+					_ => Rotation::Quarter(index),
+				});
+			}
+		}
+		None
+	}
+
+	// checks if a piece is inside the map and only intersects empty cells or the cells of the faller
+	fn fits_faller(&self, piece: &Piece) -> bool {
+		piece.points.iter().all(|p| {
+			self.bounds_contain(*p) && (!self.at(*p).is_full() || self.faller().points.contains(p))
+		})
+	}
+
+	// continuously moves a piece until it can't move anymore and return the number of moves
+	fn throw_piece(&mut self, piece_index: usize, d: Direction) -> u32 {
+		let mut moves = 0;
+		let mut moved = self.pieces[piece_index].clone();
+		while self.try_move_piece(&mut moved, d) {
+			moves += 1;
+		}
+		self.pieces[piece_index] = moved;
+		moves
+	}
+
+	fn bounds_contain(&self, V2{x,y}: V2) -> bool {
+		x < self.width as i32 && x >= 0 && y >= 0 && y < self.height as i32
+	}
+
+	fn check_row(&self, y: usize) -> bool {
+		let i = y * self.width;
+		self.map[i..i + self.width].iter().all(Cell::is_full)
+	}
+
+	fn row_has_garbage(&self, y: usize) -> bool {
+		let i = y * self.width;
+		self.map[i..i + self.width].iter().any(Cell::is_garbage)
+	}
+
+	// pushes the stack up by a row of garbage for each hole, with the hole's column left empty.
+	// only called between a lock and the next spawn, so there's no faller in the way
+	fn add_garbage(&mut self, holes: &[usize]) -> Result<(), SpawningError> {
+		let thickness = holes.len().min(self.height) * self.width;
+		if self.map[..thickness].iter().any(Cell::is_full) {
+			return Err(SpawningError::Ended(Outcome::GameOver(GameOverReason::TopOut)));
+		}
+		self.map.rotate_left(thickness);
+		let start = self.map.len() - thickness;
+		for (row, hole) in self.map[start..].chunks_mut(self.width).zip(holes) {
+			for (cell, garbage) in row.iter_mut().zip(garbage::row(self.width, *hole)) {
+				*cell = garbage;
+			}
+		}
+		Ok(())
+	}
+
+	// brings up the rows of dig mode that are still to come, as far as DIG_VISIBLE_ROWS are on the board
+	fn raise_dig_garbage(&mut self) -> Result<(), SpawningError> {
+		let on_board = self.garbage_rows - self.garbage_pending;
+		let rows = self.garbage_pending.min(DIG_VISIBLE_ROWS.saturating_sub(on_board));
+		let holes: Vec<usize> = (0..rows).map(|_| self.garbage.next_hole(self.width)).collect();
+		self.garbage_pending -= rows;
+		self.add_garbage(&holes)
+	}
+
+	// clears a number of rows and drops the rows above it
+	// should be called after check_rows() returns true
+	fn clear_rows(&mut self, y: usize, rows_down: usize) {
+		let thickness = self.width * rows_down;
+		let i = y * self.width;
+		self.map[i..i + thickness].iter_mut().for_each(|cell| cell.empty()); // clear rows
+		self.map[..i + thickness].rotate_right(thickness); // drop gravity
+	}
+
+	// applies an input, and returns whether it changed anything
+	fn handle_input(&mut self, action: GameInput) -> Result<bool, SpawningError>{
+		use Direction::*;
+		use GameInput::*;
+
+		let changed = match action {
+			Press(direction) => {
+				self.press(direction);
+				return self.handle_input(direction.input());
+			}
+			Release(direction) => {
+				self.held_moves.retain(|held| held.direction != direction);
+				false
+			}
+			SpinLeft | SpinRight | Spin180 => {
+				if let Some(rotation) = self.spin(action) {
+					self.last_rotation = Some(rotation);
+					self.update_ghost(true);
+					let (Rotation::Quarter(kick) | Rotation::Half(kick)) = rotation;
+					self.emit(GameEvent::Rotated { kick, half_turn: matches!(rotation, Rotation::Half(_)) });
+					true
+				} else {
+					false
+				}
+			}
+			MoveLeft => {
+				self.try_move_piece_and_update(FALLER_INDEX, Left)
+			}
+			MoveRight => {
+				self.try_move_piece_and_update(FALLER_INDEX, Right)
+			}
+			MoveDown => {
+				if self.try_move_piece_and_update(FALLER_INDEX, Down) {
+					let points = self.scoring.soft_drop(1);
+					self.add_score(points);
+					self.reset_gravity_time();
+					true
+				} else {
+					false
+				}
+			}
+			Hold => {
+				self.hold()?
+			}
+			DropDown => {
+				let rows = (self.ghost().pos.y - self.faller().pos.y) as u32;
+				if rows > 0 {
+					self.last_rotation = None;
+				}
+				let points = self.scoring.hard_drop(rows);
+				self.add_score(points);
+				self.update_piece(FALLER_INDEX, self.ghost().clone_as_full(), false);
+				if rows > 0 {
+					self.emit(GameEvent::Moved);
+				}
+				// todo the following two lines can be put into a function as they are also being used in apply_gravity
+				self.spawn_piece_and_update_ghost()?;
+				true
+			}
+		};
+
+		if changed && matches!(action, SpinLeft | SpinRight | Spin180 | MoveLeft | MoveRight | MoveDown) {
+			if !matches!(action, SpinLeft | SpinRight | Spin180) {
+				self.last_rotation = None;
+			}
+			self.apply_instant_gravity();
+			self.update_lock_time();
+		}
+		Ok(changed)
+	}
+
+	// starts repeating a move. the last sideways direction pressed wins
+	fn press(&mut self, direction: Move) {
+		let delay = match direction {
+			Move::Down => self.soft_drop_interval(),
+			Move::Left | Move::Right => self.das,
+		};
+		self.held_moves.retain(|held| match direction {
+			Move::Down => held.direction != Move::Down,
+			Move::Left | Move::Right => held.direction == Move::Down,
+		});
+		self.held_moves.push(HeldMove { direction, next_repeat: self.now + delay });
+	}
+
+	// repeats the moves of held keys that are due
+	fn apply_held_moves(&mut self) -> Result<(), SpawningError> {
+		for index in 0..self.held_moves.len() {
+			let HeldMove { direction, next_repeat } = self.held_moves[index];
+			if self.now < next_repeat {
+				continue;
+			}
+			let interval = match direction {
+				Move::Down => self.soft_drop_interval(),
+				Move::Left | Move::Right => self.arr,
+			};
+			if interval.is_zero() {
+				while self.handle_input(direction.input())? {}
+				self.held_moves[index].next_repeat = self.now + TICK;
+			} else {
+				// a blocked move waits for the next interval, rather than catching up on the ones it missed
+				while self.now >= self.held_moves[index].next_repeat {
+					if !self.handle_input(direction.input())? {
+						self.held_moves[index].next_repeat = self.now + interval;
+						break;
+					}
+					self.held_moves[index].next_repeat += interval;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	// applies gravity. if the piece can't fall, it starts the lock delay instead
+	fn apply_gravity(&mut self) {
+		match self.gravity() {
+			Gravity::Interval(_) => {
+				if self.try_move_piece_and_update(FALLER_INDEX, Direction::Down) {
+					self.last_rotation = None;
+				}
+			}
+			Gravity::Instant => self.apply_instant_gravity(),
+		}
+		self.update_lock_time();
+	}
+
+	fn spawn_piece_and_update_ghost(&mut self)-> Result<(), SpawningError> {
+		let r = self.spawn_random_piece(FALLER_INDEX);
+		self.update_ghost(false);
+		r
+	}
+}
+
+impl Engine {
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	// the visible rows, without the buffer
+	pub fn height(&self) -> usize {
+		self.height - self.buffer_rows
+	}
+
+	pub fn buffer_rows(&self) -> usize {
+		self.buffer_rows
+	}
+
+	// the visible board row by row from the top, with the faller and its ghost drawn in
+	pub fn rows(&self) -> impl Iterator<Item = &[Cell]> + '_ {
+		self.map[self.buffer_rows * self.width..].chunks(self.width)
+	}
+
+	// the hidden rows above the board, from the top
+	pub fn buffer(&self) -> impl Iterator<Item = &[Cell]> + '_ {
+		self.map[..self.buffer_rows * self.width].chunks(self.width)
+	}
+
+	pub fn score(&self) -> u32 {
+		self.score
+	}
+
+	// the last lock that scored points
+	pub fn last_score_event(&self) -> Option<&ScoreEvent> {
+		self.last_score_event.as_ref()
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	pub fn level(&self) -> u32 {
+		self.level
+	}
+
+	// lines cleared since the game started
+	pub fn lines(&self) -> u32 {
+		self.lines
+	}
+
+	// the piece in the hold slot, if any
+	pub fn held(&self) -> Option<Shape> {
+		self.held
+	}
+
+	// whether the faller can be swapped with the hold slot
+	pub fn can_hold(&self) -> bool {
+		self.can_hold
+	}
+
+	// the upcoming pieces, in the order they will spawn
+	pub fn next_queue(&self) -> impl Iterator<Item = Shape> + '_ {
+		self.next_queue.iter().copied()
+	}
+
+	// the time played, as counted by the ticks that ran
+	pub fn time(&self) -> Duration {
+		self.now
+	}
+
+	pub fn mode(&self) -> &GameMode {
+		&self.mode
+	}
+
+	// queues rows of garbage sent by the opponent. they come up when a piece locks without clearing rows,
+	// unless they're cancelled by attacking first
+	pub fn receive_garbage(&mut self, rows: u32) {
+		if rows > 0 && !self.ended {
+			self.incoming.push_back(rows);
+		}
+	}
+
+	// rows of garbage waiting to come up
+	pub fn incoming_garbage(&self) -> u32 {
+		self.incoming.iter().fold(0, |sum, rows| sum.saturating_add(*rows))
+	}
+
+	// rows of garbage left to clear in dig mode
+	pub fn garbage_rows(&self) -> u32 {
+		self.garbage_rows
+	}
+
+	// whether the game ended, won or lost
+	pub fn has_ended(&self) -> bool {
+		self.outcome.is_some()
+	}
+
+	pub fn outcome(&self) -> Option<Outcome> {
+		self.outcome
+	}
+
+	// returns an iterator of all bools in the current state of the game.
+	// it's lazy so if you update the game, the values iterated will change
+	pub fn serialize(&self) -> impl Iterator<Item = bool> + '_ {
+		self.map.iter().map(Cell::is_full)
+	}
+}
+
+#[derive(Debug)]
+enum SpawningError {
+	Ended(Outcome),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use GameEvent::*;
+	use GameInput::*;
+
+	// deals the given shapes over and over, so a test knows what comes next
+	struct Sequence {
+		shapes: Vec<Shape>,
+		next: usize,
+	}
+
+	impl Randomizer for Sequence {
+		fn next_shape(&mut self, _rng: &mut dyn rand::RngCore) -> Shape {
+			let shape = self.shapes[self.next % self.shapes.len()];
+			self.next += 1;
+			shape
+		}
+
+		fn reset(&mut self) {
+			self.next = 0;
+		}
+
+		fn name(&self) -> String {
+			"sequence".to_string()
+		}
+
+		fn save(&self) -> serde_json::Value {
+			serde_json::Value::Null
+		}
+
+		fn restore(&mut self, _state: serde_json::Value) -> Result<(), String> {
+			Ok(())
+		}
+	}
+
+	// a small board, 4 wide so two O pieces fill a row
+	fn engine(shapes: &[Shape]) -> Engine {
+		let settings = Settings { width: 4, height: 6, ..Settings::default() };
+		let randomizer = Sequence { shapes: shapes.to_vec(), next: 0 };
+		let mut engine = Engine::new(Box::new(randomizer), scoring::from_name("guideline").unwrap(), 0, settings);
+		engine.reset();
+		engine
+	}
+
+	// the visible board with # for the stack and the faller, o for the ghost
+	fn board(engine: &Engine) -> Vec<String> {
+		engine.rows().map(|row| row.iter().map(|cell| match cell {
+			Cell::Empty => '.',
+			Cell::Ghost(_) => 'o',
+			_ => '#',
+		}).collect()).collect()
+	}
+
+	// reset leaves the first spawn among the events, which the tests don't look at
+	fn started(shapes: &[Shape]) -> Engine {
+		let mut engine = engine(shapes);
+		engine.step(&[], Duration::ZERO);
+		engine
+	}
+
+	#[test]
+	fn moves_until_blocked() {
+		let mut engine = started(&[Shape::O]);
+		assert_eq!(engine.step(&[MoveLeft, MoveLeft, MoveLeft], Duration::ZERO), vec![Moved, Moved]);
+		assert_eq!(engine.step(&[MoveRight], Duration::ZERO), vec![Moved]);
+		assert_eq!(engine.step(&[MoveDown], Duration::ZERO), vec![Moved, ScoreChanged { score: 1, points: 1 }]);
+		assert_eq!(board(&engine), [".##.", "....", "....", "....", ".oo.", ".oo."]);
+		assert_eq!(engine.faller().pos.y, 1);
+	}
+
+	#[test]
+	fn gravity_moves_the_faller_down() {
+		let mut engine = started(&[Shape::O]);
+		// a row a second at level 1, on the first tick after it
+		assert_eq!(engine.step(&[], Duration::from_millis(1000)), vec![]);
+		assert_eq!(engine.step(&[], Duration::from_millis(20)), vec![Moved]);
+		assert_eq!(engine.faller().pos.y, 1);
+	}
+
+	#[test]
+	fn hard_drop_locks_and_spawns_the_next_piece() {
+		let mut engine = started(&[Shape::O, Shape::T]);
+		assert_eq!(
+			engine.step(&[MoveLeft, MoveLeft, DropDown], Duration::ZERO),
+			vec![Moved, Moved, ScoreChanged { score: 12, points: 12 }, Moved, Locked(Shape::O), PieceSpawned(Shape::T)],
+		);
+		// the T's ghost rests on the O
+		assert_eq!(board(&engine), ["....", "....", "....", ".ooo", "##o.", "##.."]);
+		assert_eq!(engine.score(), 12);
+	}
+
+	#[test]
+	fn resting_piece_locks_after_the_lock_delay() {
+		let mut engine = started(&[Shape::O]);
+		engine.step(&[MoveDown; 6], Duration::ZERO);
+		assert_eq!(engine.step(&[], Duration::from_millis(490)), vec![]);
+		// the delay starts on the first tick the piece rests
+		assert_eq!(engine.step(&[], Duration::from_millis(40)), vec![Locked(Shape::O), PieceSpawned(Shape::O)]);
+		assert_eq!(board(&engine), ["....", "....", "..oo", "..oo", "..##", "..##"]);
+	}
+
+	#[test]
+	fn full_rows_are_cleared_and_scored() {
+		let mut engine = started(&[Shape::O]);
+		engine.step(&[MoveLeft, MoveLeft, DropDown, MoveLeft, MoveLeft, DropDown], Duration::ZERO);
+		assert_eq!(engine.score(), 20);
+		let events = engine.step(&[DropDown], Duration::ZERO);
+		assert!(events.contains(&LinesCleared { rows: 2, kind: Spin::None }));
+		assert_eq!(board(&engine), ["....", "....", "....", "....", "##oo", "##oo"]);
+		assert_eq!(engine.lines(), 2);
+		// 12 for the hard drop and 300 for a double at level 1
+		assert_eq!(engine.score(), 20 + 12 + 300);
+	}
+
+	#[test]
+	fn hold_swaps_the_faller_once_per_piece() {
+		let mut engine = started(&[Shape::O, Shape::T, Shape::S]);
+		assert_eq!(engine.step(&[GameInput::Hold], Duration::ZERO), vec![GameEvent::Hold { held: Shape::O }, PieceSpawned(Shape::T)]);
+		assert_eq!(engine.held(), Some(Shape::O));
+		assert!(!engine.can_hold());
+		assert_eq!(engine.step(&[GameInput::Hold], Duration::ZERO), vec![]);
+
+		engine.step(&[DropDown], Duration::ZERO);
+		assert!(engine.can_hold());
+		assert_eq!(engine.step(&[GameInput::Hold], Duration::ZERO), vec![GameEvent::Hold { held: Shape::S }, PieceSpawned(Shape::O)]);
+		assert_eq!(engine.held(), Some(Shape::S));
+	}
+
+	#[test]
+	fn next_queue_shows_what_spawns_next() {
+		let mut engine = started(&[Shape::O, Shape::T, Shape::S, Shape::Z, Shape::J, Shape::L]);
+		assert_eq!(engine.next_queue().collect::<Vec<_>>(), [Shape::T, Shape::S, Shape::Z, Shape::J, Shape::L]);
+		let events = engine.step(&[DropDown], Duration::ZERO);
+		assert_eq!(events.last(), Some(&PieceSpawned(Shape::T)));
+		assert_eq!(engine.next_queue().collect::<Vec<_>>(), [Shape::S, Shape::Z, Shape::J, Shape::L, Shape::O]);
+	}
+
+	#[test]
+	fn held_moves_stop_at_a_wall_after_a_long_step() {
+		// zen, so the game goes on for the whole hour
+		let settings = Settings {
+			width: 4, height: 6, arr: Duration::from_nanos(1), soft_drop_factor: u32::MAX - 1, mode: GameMode::Zen, ..Settings::default()
+		};
+		let mut engine = Engine::new(Box::new(Sequence { shapes: vec![Shape::O], next: 0 }), scoring::from_name("guideline").unwrap(), 0, settings);
+		engine.reset();
+		assert_eq!(engine.soft_drop_interval(), TICK);
+		engine.step(&[Press(Move::Left)], Duration::from_millis(500));
+		assert_eq!(engine.faller().points.iter().map(|p| p.x).min(), Some(0));
+		engine.step(&[], Duration::from_secs(3600));
+		engine.step(&[Press(Move::Down)], Duration::from_secs(3600));
+		assert!(!engine.has_ended());
+	}
+
+	#[test]
+	fn released_moves_stop_repeating() {
+		let mut engine = started(&[Shape::O]);
+		engine.step(&[Press(Move::Left), Press(Move::Down)], Duration::ZERO);
+		engine.release_moves();
+		assert!(engine.snapshot().held_moves.is_empty());
+		assert_eq!(engine.inputs()[2..], [(0, Release(Move::Left)), (0, Release(Move::Down))]);
+		assert_eq!(engine.step(&[], Duration::from_millis(500)), vec![]);
+	}
+
+	#[test]
+	fn stacking_above_the_board_ends_the_game() {
+		let mut engine = started(&[Shape::O]);
+		engine.step(&[DropDown, DropDown], Duration::ZERO);
+		assert!(!engine.has_ended());
+		let events = engine.step(&[DropDown, DropDown], Duration::ZERO);
+		assert_eq!(events.last(), Some(&GameOver { reason: GameOverReason::LockOut }));
+		assert_eq!(engine.outcome(), Some(Outcome::GameOver(GameOverReason::LockOut)));
+		assert_eq!(engine.step(&[MoveLeft], Duration::from_secs(1)), vec![]);
+	}
+
+	#[test]
+	fn garbage_leaves_the_pieces_alone() {
+		let zen = || {
+			let settings = Settings { mode: GameMode::Zen, ..Settings::default() };
+			let mut engine = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 7, settings);
+			engine.reset();
+			engine
+		};
+		let spawned = |engine: &mut Engine| -> Vec<Shape> {
+			(0..30).flat_map(|_| engine.step(&[DropDown], Duration::ZERO))
+				.filter_map(|event| match event { PieceSpawned(shape) => Some(shape), _ => None })
+				.collect()
+		};
+		let (mut first, mut second) = (zen(), zen());
+		second.receive_garbage(2);
+		assert_eq!(spawned(&mut first), spawned(&mut second));
+		assert_eq!(second.incoming_garbage(), 0);
+	}
+
+	// a T on a 6x6 board over the given stack, picked up from a snapshot so it can be anywhere. the rows are the bottom of the
+	// board, and the T's center is in visible rows with the rotation of its offsets: 0 points down, 1 right
+	fn t_over(stack: &[&str], rotation: i8, center: V2) -> Engine {
+		let settings = Settings { width: 6, height: 6, ..Settings::default() };
+		let mut engine = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 0, settings);
+		engine.reset();
+		let mut snapshot = engine.snapshot();
+		snapshot.map = vec![Cell::Empty; snapshot.map.len()];
+		let top = engine.height - stack.len();
+		for (y, row) in stack.iter().enumerate() {
+			for (x, cell) in row.chars().enumerate() {
+				if cell == '#' {
+					snapshot.map[x + (top + y) * engine.width] = Cell::Garbage;
+				}
+			}
+		}
+		snapshot.faller = SavedPiece { shape: Shape::T, rotation, pos: center + V2::new(0, engine.buffer_rows as i32) };
+		Engine::from_snapshot(snapshot).unwrap()
+	}
+
+	#[test]
+	fn t_spin_double_needs_both_corners_it_points_to() {
+		let mut engine = t_over(&[".#....", "#...##", "##.###"], 1, V2::new(2, 4));
+		assert_eq!(engine.step(&[SpinLeft], Duration::ZERO), vec![Rotated { kick: 0, half_turn: false }]);
+		let events = engine.step(&[DropDown], Duration::ZERO);
+		assert!(events.contains(&LinesCleared { rows: 2, kind: Spin::Full }));
+		assert!(events.contains(&Attack { rows: 4 }));
+		assert_eq!(engine.score(), 1200);
+		assert_eq!(engine.lines(), 2);
+	}
+
+	#[test]
+	fn t_spin_with_one_corner_in_front_is_a_mini() {
+		// the T only gets under the overhang with the second kick
+		let mut engine = t_over(&["#.....", "#.....", "#.####"], 2, V2::new(2, 4));
+		assert_eq!(engine.step(&[SpinLeft], Duration::ZERO), vec![Rotated { kick: 1, half_turn: false }]);
+		let events = engine.step(&[DropDown], Duration::ZERO);
+		assert!(events.contains(&LinesCleared { rows: 1, kind: Spin::Mini }));
+		assert_eq!(engine.score(), 200);
+	}
+
+	#[test]
+	fn moving_after_a_rotation_isnt_a_spin() {
+		// the same spot as the mini, but the T drops into it after turning
+		let mut engine = t_over(&["#.....", "#.....", "#.####"], 2, V2::new(2, 1));
+		engine.step(&[SpinLeft, MoveLeft], Duration::ZERO);
+		let events = engine.step(&[DropDown], Duration::ZERO);
+		assert!(events.contains(&LinesCleared { rows: 1, kind: Spin::None }));
+		// 6 for the 3 rows of the hard drop and 100 for a single
+		assert_eq!(engine.score(), 6 + 100);
+	}
+}
@@ -0,0 +1,66 @@
+// Rows of garbage pushed up from the bottom of the board, full but for one hole.
+// A clean generator keeps the hole in the same column, so the rows can be cleared a few at a time,
+// a messy one moves it to another column most rows
+
+use super::cell::Cell;
+use super::scoring::{ScoreEvent, Spin};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+
+// extra rows sent for the combo, by the number of clears in a row before this one
+const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const BACK_TO_BACK_ATTACK: u32 = 1;
+const PERFECT_CLEAR_ATTACK: u32 = 10;
+// the holes are drawn from another stream of the game's seed than the pieces
+const GARBAGE_STREAM: u64 = 1;
+
+// the rows of garbage a lock that cleared rows sends to the opponent, by the guideline attack table
+pub fn attack(event: &ScoreEvent) -> u32 {
+	let clear = match (event.spin, event.rows) {
+		(_, 0) => return 0,
+		(Spin::Full, rows) => rows * 2,
+		(Spin::None, 4) => 4,
+		(Spin::None | Spin::Mini, rows) => rows - 1,
+	};
+	let back_to_back = if event.back_to_back { BACK_TO_BACK_ATTACK } else { 0 };
+	let combo = COMBO_ATTACK[(event.combo as usize).min(COMBO_ATTACK.len() - 1)];
+	let perfect_clear = if event.perfect_clear { PERFECT_CLEAR_ATTACK } else { 0 };
+	clear + back_to_back + combo + perfect_clear
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GarbageGenerator {
+	messiness: u32, // the chance in percent that the hole moves from one row to the next
+	hole: Option<usize>, // the hole of the last row
+	// the holes have randomness of their own, so taking garbage doesn't change the pieces that come after it
+	// and both players of a versus game keep getting the same ones
+	rng: ChaCha8Rng,
+}
+
+impl GarbageGenerator {
+	pub fn new(messiness: u32, seed: u64) -> Self {
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		rng.set_stream(GARBAGE_STREAM);
+		Self { messiness: messiness.min(100), hole: None, rng }
+	}
+
+	// picks the hole of the next row
+	pub fn next_hole(&mut self, width: usize) -> usize {
+		let rng = &mut self.rng;
+		let hole = match self.hole {
+			Some(hole) if rng.gen_range(0..100) >= self.messiness => hole,
+			// a moved hole never stays in the same column
+			Some(hole) => (hole + rng.gen_range(1..width.max(2))) % width,
+			None => rng.gen_range(0..width),
+		};
+		self.hole = Some(hole);
+		hole
+	}
+}
+
+// a row of garbage cells with an empty cell at the hole
+pub fn row(width: usize, hole: usize) -> impl Iterator<Item = Cell> {
+	(0..width).map(move |x| if x == hole { Cell::Empty } else { Cell::Garbage })
+}
@@ -0,0 +1,67 @@
+// How fast pieces fall at each level
+
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+
+// gravity faster than this many rows per second is treated as 20G- a row every 1/20 of a 60Hz frame
+const TWENTY_G_ROWS_PER_SECOND: f64 = 60.0 * 20.0;
+const NES_FRAMES_PER_SECOND: f64 = 60.0988;
+
+// frames it takes a piece to fall one row on the NES, for levels 0 to 29 and above
+const NES_FRAMES_PER_ROW: [u32; 30] = [
+	48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+	5, 5, 5, 4, 4, 4, 3, 3, 3, 2,
+	2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gravity {
+	Interval(Duration), // the piece falls one row every interval
+	Instant, // 20G: the piece drops to the bottom as soon as it appears or moves
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GravityCurve {
+	Guideline, // (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row
+	Nes, // the NES frame table, where level 0 is the slowest
+	Custom(Vec<Duration>), // an interval per level starting at level 1, the last one is kept for higher levels. zero means 20G
+}
+
+impl GravityCurve {
+	// parses "guideline", "nes", "20g", or a comma separated list of milliseconds per row
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"guideline" => Some(Self::Guideline),
+			"nes" => Some(Self::Nes),
+			"20g" | "20G" => Some(Self::Custom(vec![Duration::ZERO])),
+			_ => {
+				let table = name.split(',')
+					.map(|millis| millis.trim().parse().ok().map(Duration::from_millis))
+					.collect::<Option<Vec<_>>>()?;
+				Some(Self::Custom(table))
+			}
+		}
+	}
+
+	pub fn gravity(&self, level: u32) -> Gravity {
+		let interval = match self {
+			Self::Guideline => {
+				let level = level.max(1) as f64;
+				Duration::from_secs_f64((0.8 - (level - 1.0) * 0.007).max(0.0).powf(level - 1.0))
+			}
+			Self::Nes => {
+				let frames = NES_FRAMES_PER_ROW[(level as usize).min(NES_FRAMES_PER_ROW.len() - 1)];
+				Duration::from_secs_f64(frames as f64 / NES_FRAMES_PER_SECOND)
+			}
+			Self::Custom(table) => {
+				let index = (level.max(1) as usize - 1).min(table.len().saturating_sub(1));
+				table.get(index).copied().unwrap_or(Duration::ZERO)
+			}
+		};
+		if interval.as_secs_f64() * TWENTY_G_ROWS_PER_SECOND <= 1.0 {
+			Gravity::Instant
+		} else {
+			Gravity::Interval(interval)
+		}
+	}
+}
@@ -0,0 +1,98 @@
+// Tells the engine when the move keys go down and up, so it can repeat the held moves by the DAS and ARR.
+//
+// terminals with the kitty keyboard protocol tell when a key is released. other terminals only send a key again
+// once their own key repeat kicks in, so there a press is taken as a tap, a key that comes again quicker than
+// it can be tapped as held by the terminal's repeat, and a held key as released once the terminal stops repeating it.
+// times are by the front-end's clock
+
+use super::{GameInput, Move};
+
+use std::time::Duration;
+
+// a key the terminal stopped repeating for this long was released
+const TERMINAL_RELEASE_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct Key {
+	direction: Move,
+	last_seen: Duration,
+	held: bool, // known to be held down rather than tapped
+}
+
+#[derive(Default)]
+pub struct HeldKeys {
+	keys: Vec<Key>,
+	releases_supported: bool, // set once the terminal reports a release
+}
+
+impl HeldKeys {
+	// a move key was sent as pressed. returns the inputs for the engine
+	pub fn press(&mut self, direction: Move, now: Duration) -> Vec<GameInput> {
+		match self.keys.iter_mut().find(|key| key.direction == direction) {
+			Some(key) if key.held => {
+				key.last_seen = now;
+				Vec::new()
+			}
+			Some(key) if now.saturating_sub(key.last_seen) <= TERMINAL_RELEASE_TIMEOUT => {
+				key.held = true;
+				key.last_seen = now;
+				vec![GameInput::Press(direction)]
+			}
+			_ => {
+				self.keys.retain(|key| key.direction != direction);
+				self.keys.push(Key { direction, last_seen: now, held: self.releases_supported });
+				if self.releases_supported {
+					vec![GameInput::Press(direction)]
+				} else {
+					vec![GameInput::Press(direction), GameInput::Release(direction)]
+				}
+			}
+		}
+	}
+
+	// the terminal reported the key as repeated, so it's held
+	pub fn repeat(&mut self, direction: Move, now: Duration) -> Vec<GameInput> {
+		match self.keys.iter_mut().find(|key| key.direction == direction) {
+			Some(key) if key.held => {
+				key.last_seen = now;
+				Vec::new()
+			}
+			Some(key) => {
+				key.held = true;
+				key.last_seen = now;
+				vec![GameInput::Press(direction)]
+			}
+			None => {
+				self.keys.push(Key { direction, last_seen: now, held: true });
+				vec![GameInput::Press(direction)]
+			}
+		}
+	}
+
+	pub fn release(&mut self, direction: Move) -> Vec<GameInput> {
+		self.releases_supported = true;
+		let held = self.keys.iter().any(|key| key.direction == direction && key.held);
+		self.keys.retain(|key| key.direction != direction);
+		if held { vec![GameInput::Release(direction)] } else { Vec::new() }
+	}
+
+	// forgets every key, like when the game is paused. the engine lets go of their moves itself
+	pub fn clear(&mut self) {
+		self.keys.clear();
+	}
+
+	// the keys the terminal stopped repeating by now, when it doesn't report releases
+	pub fn due(&mut self, now: Duration) -> Vec<GameInput> {
+		if self.releases_supported {
+			return Vec::new();
+		}
+		let mut releases = Vec::new();
+		self.keys.retain(|key| {
+			let released = now.saturating_sub(key.last_seen) > TERMINAL_RELEASE_TIMEOUT;
+			if released && key.held {
+				releases.push(GameInput::Release(key.direction));
+			}
+			!released
+		});
+		releases
+	}
+}
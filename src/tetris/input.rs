@@ -0,0 +1,445 @@
+// Decodes the bytes a terminal sends for key presses, including escape sequences that are split across reads
+
+use std::time;
+
+const ESC: u8 = 0x1B;
+// the longest escape sequence kept around while waiting for the rest of it
+const MAX_SEQUENCE_LENGTH: usize = 32;
+// a lone escape byte that isn't followed by anything for this long is the escape key
+const ESCAPE_TIMEOUT: time::Duration = time::Duration::from_millis(50);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+	pub shift: bool,
+	pub alt: bool,
+	pub ctrl: bool,
+}
+
+impl Modifiers {
+	pub const NONE: Self = Self { shift: false, alt: false, ctrl: false };
+	pub const CTRL: Self = Self { shift: false, alt: false, ctrl: true };
+
+	// xterm sends modifiers as 1 + a bitmask of shift = 1, alt = 2, ctrl = 4
+	fn from_param(param: u32) -> Self {
+		let bits = param.saturating_sub(1);
+		Self {
+			shift: bits & 1 != 0,
+			alt: bits & 2 != 0,
+			ctrl: bits & 4 != 0,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+	Char(char),
+	Up, Down, Left, Right,
+	Home, End, PageUp, PageDown, Insert, Delete,
+	Backspace, Enter, Tab, BackTab, Esc,
+	F(u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+	pub code: KeyCode,
+	pub modifiers: Modifiers,
+}
+
+impl Key {
+	pub fn new(code: KeyCode, modifiers: Modifiers) -> Self {
+		Self { code, modifiers }
+	}
+}
+
+impl From<KeyCode> for Key {
+	fn from(code: KeyCode) -> Self {
+		Self::new(code, Modifiers::NONE)
+	}
+}
+
+// terminals only tell repeats and releases apart with the kitty keyboard protocol,
+// otherwise every key they send is a press
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyState {
+	Pressed,
+	Repeated,
+	Released,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+	pub key: Key,
+	pub state: KeyState,
+}
+
+impl From<Key> for KeyEvent {
+	fn from(key: Key) -> Self {
+		Self { key, state: KeyState::Pressed }
+	}
+}
+
+// asks the terminal to send every key as an escape sequence, with its repeats and releases (kitty keyboard protocol).
+// terminals that don't support it ignore it
+pub const ENABLE_KEY_RELEASES: &str = "\x1b[>11u";
+pub const DISABLE_KEY_RELEASES: &str = "\x1b[<u";
+
+enum Parsed {
+	Key(KeyEvent, usize), // a key and the number of bytes it took
+	Skip(usize), // bytes of a sequence that isn't a key we know
+	Incomplete, // the start of a sequence, the rest hasn't arrived yet
+}
+
+#[derive(Default)]
+pub struct Decoder {
+	buffer: Vec<u8>,
+	pending_since: Option<time::Duration>, // when the bytes waiting for the rest of their sequence arrived, by the front-end's clock
+	fed_at: Option<time::Duration>, // when the last bytes arrived
+}
+
+impl Decoder {
+	// adds bytes read from the terminal
+	pub fn feed(&mut self, bytes: &[u8], now: time::Duration) {
+		if bytes.is_empty() {
+			return;
+		}
+		if self.buffer.is_empty() {
+			self.pending_since = Some(now);
+		}
+		self.fed_at = Some(now);
+		self.buffer.extend_from_slice(bytes);
+	}
+
+	// decodes the next key, or returns None if there are no bytes or only the start of a sequence
+	pub fn next_key(&mut self) -> Option<KeyEvent> {
+		loop {
+			match parse(&self.buffer) {
+				Parsed::Key(key, length) => {
+					self.consume(length);
+					return Some(key);
+				}
+				Parsed::Skip(length) => self.consume(length),
+				Parsed::Incomplete => return None,
+			}
+		}
+	}
+
+	// once a sequence waited too long for the rest of it, decodes its first byte on its own.
+	// this is how a lone escape becomes the escape key
+	pub fn flush_stale(&mut self, now: time::Duration) -> Option<KeyEvent> {
+		match self.pending_since {
+			Some(since) if now.saturating_sub(since) >= ESCAPE_TIMEOUT && !self.buffer.is_empty() => {
+				let key = (self.buffer[0] == ESC).then(|| KeyEvent::from(Key::from(KeyCode::Esc)));
+				self.consume(1);
+				key.or_else(|| self.next_key())
+			}
+			_ => None,
+		}
+	}
+
+	fn consume(&mut self, length: usize) {
+		self.buffer.drain(..length.min(self.buffer.len()));
+		// what's left could have come with the last bytes, so it waits from then
+		self.pending_since = if self.buffer.is_empty() { None } else { self.fed_at };
+	}
+}
+
+fn parse(bytes: &[u8]) -> Parsed {
+	use KeyCode::*;
+
+	let key = |code, length| Parsed::Key(Key::from(code).into(), length);
+	let ctrl = |c: u8| Parsed::Key(Key::new(Char(c as char), Modifiers::CTRL).into(), 1);
+
+	let Some(&first) = bytes.first() else {
+		return Parsed::Incomplete;
+	};
+	match first {
+		ESC => match bytes.get(1) {
+			None => Parsed::Incomplete,
+			Some(b'[') => parse_csi(bytes),
+			Some(b'O') => parse_ss3(bytes),
+			Some(&ESC) => key(Esc, 1),
+			// escape followed by a key is that key with alt held
+			Some(_) => match parse(&bytes[1..]) {
+				Parsed::Key(mut event, length) => {
+					event.key.modifiers.alt = true;
+					Parsed::Key(event, length + 1)
+				}
+				Parsed::Skip(length) => Parsed::Skip(length + 1),
+				Parsed::Incomplete => Parsed::Incomplete,
+			}
+		}
+		b'\r' | b'\n' => key(Enter, 1),
+		b'\t' => key(Tab, 1),
+		0x7F | 0x08 => key(Backspace, 1),
+		0x00 => ctrl(b' '),
+		0x01..=0x1A => ctrl(first - 0x01 + b'a'),
+		0x1C..=0x1F => ctrl(first - 0x1C + b'4'),
+		_ => parse_utf8(bytes),
+	}
+}
+
+// a character that can take 1 to 4 bytes
+fn parse_utf8(bytes: &[u8]) -> Parsed {
+	let length = match bytes[0] {
+		0x00..=0x7F => 1,
+		0xC0..=0xDF => 2,
+		0xE0..=0xEF => 3,
+		0xF0..=0xF7 => 4,
+		_ => return Parsed::Skip(1),
+	};
+	if bytes.len() < length {
+		return Parsed::Incomplete;
+	}
+	match std::str::from_utf8(&bytes[..length]).ok().and_then(|s| s.chars().next()) {
+		Some(c) => Parsed::Key(Key::from(KeyCode::Char(c)).into(), length),
+		None => Parsed::Skip(length),
+	}
+}
+
+// ESC O and a letter, sent for F1 to F4 and by terminals in application cursor mode
+fn parse_ss3(bytes: &[u8]) -> Parsed {
+	use KeyCode::*;
+
+	let Some(&last) = bytes.get(2) else {
+		return Parsed::Incomplete;
+	};
+	let code = match last {
+		b'A' => Up,
+		b'B' => Down,
+		b'C' => Right,
+		b'D' => Left,
+		b'H' => Home,
+		b'F' => End,
+		b'P'..=b'S' => F(1 + last - b'P'),
+		_ => return Parsed::Skip(3),
+	};
+	Parsed::Key(Key::from(code).into(), 3)
+}
+
+// ESC [, numbers separated by semicolons, and a final byte that tells what the numbers mean
+fn parse_csi(bytes: &[u8]) -> Parsed {
+	use KeyCode::*;
+
+	// the linux console sends F1 to F5 as ESC [ [ and a letter, where [ isn't the final byte
+	if bytes.get(2) == Some(&b'[') {
+		return match bytes.get(3) {
+			None => Parsed::Incomplete,
+			Some(&last @ b'A'..=b'E') => Parsed::Key(Key::from(F(1 + last - b'A')).into(), 4),
+			Some(_) => Parsed::Skip(4),
+		};
+	}
+	let Some(end) = bytes[2..].iter().position(|b| (0x40..=0x7E).contains(b)).map(|i| i + 2) else {
+		return if bytes.len() >= MAX_SEQUENCE_LENGTH { Parsed::Skip(bytes.len()) } else { Parsed::Incomplete };
+	};
+	let length = end + 1;
+	// each parameter can have sub-parameters after colons, like the kitty protocol's modifiers:event type
+	let params: Vec<Vec<u32>> = std::str::from_utf8(&bytes[2..end]).unwrap_or("")
+		.split(';')
+		.map(|param| param.split(':').map(|sub| sub.parse().unwrap_or(1)).collect())
+		.collect();
+	let sub_param = |index: usize, sub: usize| params.get(index).and_then(|p| p.get(sub)).copied().unwrap_or(1);
+	let param = |index: usize| sub_param(index, 0);
+	let modifiers = Modifiers::from_param(param(1));
+	let state = match sub_param(1, 1) {
+		2 => KeyState::Repeated,
+		3 => KeyState::Released,
+		_ => KeyState::Pressed,
+	};
+
+	let code = match bytes[end] {
+		b'A' => Up,
+		b'B' => Down,
+		b'C' => Right,
+		b'D' => Left,
+		b'H' => Home,
+		b'F' => End,
+		b'Z' => BackTab,
+		b'P'..=b'S' => F(1 + bytes[end] - b'P'),
+		b'~' => match param(0) {
+			1 | 7 => Home,
+			2 => Insert,
+			3 => Delete,
+			4 | 8 => End,
+			5 => PageUp,
+			6 => PageDown,
+			code @ 11..=15 => F(code as u8 - 10),
+			code @ 17..=21 => F(code as u8 - 11),
+			code @ 23..=24 => F(code as u8 - 12),
+			_ => return Parsed::Skip(length),
+		}
+		// the kitty keyboard protocol: the unicode value of the key
+		b'u' => match param(0) {
+			9 => Tab,
+			13 => Enter,
+			27 => Esc,
+			127 => Backspace,
+			code => match char::from_u32(code) {
+				Some(c) => Char(c),
+				None => return Parsed::Skip(length),
+			}
+		}
+		_ => return Parsed::Skip(length),
+	};
+	Parsed::Key(KeyEvent { key: Key::new(code, modifiers), state }, length)
+}
+
+// key names as written in the key bindings file: a character, or a name like Left, Space or F5,
+// after any of the modifiers Ctrl+, Alt+ and Shift+
+impl std::str::FromStr for Key {
+	type Err = ();
+
+	fn from_str(name: &str) -> Result<Self, ()> {
+		use KeyCode::*;
+
+		let mut modifiers = Modifiers::NONE;
+		let mut rest = name;
+		// a lone "+" is the plus key, not a separator
+		while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+			match modifier.to_ascii_lowercase().as_str() {
+				"ctrl" => modifiers.ctrl = true,
+				"alt" => modifiers.alt = true,
+				"shift" => modifiers.shift = true,
+				_ => return Err(()),
+			}
+			rest = key;
+		}
+
+		let mut chars = rest.chars();
+		let code = match (chars.next(), chars.next()) {
+			(Some(c), None) => Char(c),
+			_ => match rest.to_ascii_lowercase().as_str() {
+				"space" => Char(' '),
+				"up" => Up,
+				"down" => Down,
+				"left" => Left,
+				"right" => Right,
+				"home" => Home,
+				"end" => End,
+				"pageup" => PageUp,
+				"pagedown" => PageDown,
+				"insert" => Insert,
+				"delete" => Delete,
+				"backspace" => Backspace,
+				"enter" => Enter,
+				"tab" => Tab,
+				"backtab" => BackTab,
+				"esc" | "escape" => Esc,
+				name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+					Some(n @ 1..=12) => F(n),
+					_ => return Err(()),
+				}
+			}
+		};
+		Ok(Key::new(code, modifiers))
+	}
+}
+
+impl std::fmt::Display for Key {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		use KeyCode::*;
+
+		if self.modifiers.ctrl {
+			write!(f, "Ctrl+")?;
+		}
+		if self.modifiers.alt {
+			write!(f, "Alt+")?;
+		}
+		if self.modifiers.shift {
+			write!(f, "Shift+")?;
+		}
+		match self.code {
+			Char(' ') => write!(f, "Space"),
+			Char(c) => write!(f, "{}", c),
+			F(n) => write!(f, "F{}", n),
+			code => write!(f, "{:?}", code),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use KeyCode::*;
+
+	const MS: time::Duration = time::Duration::from_millis(1);
+
+	// the keys the bytes decode to, when they arrive all at once
+	fn keys(bytes: &[u8]) -> Vec<KeyEvent> {
+		let mut decoder = Decoder::default();
+		decoder.feed(bytes, time::Duration::ZERO);
+		std::iter::from_fn(|| decoder.next_key()).collect()
+	}
+
+	fn pressed(code: KeyCode, modifiers: Modifiers) -> KeyEvent {
+		KeyEvent { key: Key::new(code, modifiers), state: KeyState::Pressed }
+	}
+
+	#[test]
+	fn arrows() {
+		let none = Modifiers::NONE;
+		assert_eq!(keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"), [pressed(Up, none), pressed(Down, none), pressed(Right, none), pressed(Left, none)]);
+		// application cursor mode
+		assert_eq!(keys(b"\x1bOA\x1bOD"), [pressed(Up, none), pressed(Left, none)]);
+	}
+
+	#[test]
+	fn modifiers() {
+		let shift = Modifiers { shift: true, ..Modifiers::NONE };
+		let ctrl_alt = Modifiers { alt: true, ctrl: true, ..Modifiers::NONE };
+		assert_eq!(keys(b"\x1b[1;2C\x1b[1;7A"), [pressed(Right, shift), pressed(Up, ctrl_alt)]);
+		assert_eq!(keys(b"\x01\x1bx"), [pressed(Char('a'), Modifiers::CTRL), pressed(Char('x'), Modifiers { alt: true, ..Modifiers::NONE })]);
+	}
+
+	#[test]
+	fn kitty_presses_repeats_and_releases() {
+		let key = |state| KeyEvent { key: Key::from(Char('a')), state };
+		assert_eq!(keys(b"\x1b[97u\x1b[97;1:2u\x1b[97;1:3u"), [key(KeyState::Pressed), key(KeyState::Repeated), key(KeyState::Released)]);
+		let shift = Modifiers { shift: true, ..Modifiers::NONE };
+		assert_eq!(keys(b"\x1b[97;2:3u"), [KeyEvent { key: Key::new(Char('a'), shift), state: KeyState::Released }]);
+		assert_eq!(keys(b"\x1b[1;1:3D"), [KeyEvent { key: Key::from(Left), state: KeyState::Released }]);
+	}
+
+	#[test]
+	fn linux_console_function_keys() {
+		assert_eq!(keys(b"\x1b[[A\x1b[[E"), [pressed(F(1), Modifiers::NONE), pressed(F(5), Modifiers::NONE)]);
+	}
+
+	#[test]
+	fn sequences_split_across_reads() {
+		let mut decoder = Decoder::default();
+		decoder.feed(b"\x1b", time::Duration::ZERO);
+		assert_eq!(decoder.next_key(), None);
+		decoder.feed(b"[1;", MS);
+		assert_eq!(decoder.next_key(), None);
+		assert_eq!(decoder.flush_stale(2 * MS), None);
+		decoder.feed(b"5Dx", 3 * MS);
+		let ctrl = Modifiers::CTRL;
+		assert_eq!(decoder.next_key(), Some(pressed(Left, ctrl)));
+		assert_eq!(decoder.next_key(), Some(pressed(Char('x'), Modifiers::NONE)));
+		assert_eq!(decoder.next_key(), None);
+	}
+
+	#[test]
+	fn lone_escape_is_the_escape_key_after_a_while() {
+		let mut decoder = Decoder::default();
+		decoder.feed(b"\x1b", time::Duration::ZERO);
+		assert_eq!(decoder.next_key(), None);
+		assert_eq!(decoder.flush_stale(ESCAPE_TIMEOUT - MS), None);
+		assert_eq!(decoder.flush_stale(ESCAPE_TIMEOUT), Some(pressed(Esc, Modifiers::NONE)));
+		assert_eq!(decoder.next_key(), None);
+	}
+
+	#[test]
+	fn leftover_escape_waits_from_when_it_came() {
+		let mut decoder = Decoder::default();
+		decoder.feed(b"\x1b[", time::Duration::ZERO);
+		assert_eq!(decoder.next_key(), None);
+		// the rest of the sequence comes with the start of the next one
+		decoder.feed(b"A\x1b", 40 * MS);
+		assert_eq!(decoder.next_key(), Some(pressed(Up, Modifiers::NONE)));
+		assert_eq!(decoder.next_key(), None);
+		assert_eq!(decoder.flush_stale(ESCAPE_TIMEOUT), None);
+		decoder.feed(b"[B", ESCAPE_TIMEOUT + MS);
+		assert_eq!(decoder.next_key(), Some(pressed(Down, Modifiers::NONE)));
+	}
+}
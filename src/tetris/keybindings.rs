@@ -0,0 +1,334 @@
+// Which keys do what, loaded from a TOML file in the user's config directory.
+// Each action takes a key or a list of keys, for example:
+//
+//   move_left = ["Left", "a"]
+//   hold = "Shift+Up"
+//
+// actions missing from the file keep their default keys.
+// two players share a keyboard with their own keys, from a file with a table for each:
+//
+//   [player1]
+//   move_left = "a"
+//   [player2]
+//   move_left = "Left"
+
+use super::input::{Key, KeyCode};
+use super::GameInput;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+	Game(GameInput),
+	Pause,
+	Quit,
+}
+
+// every action with its name in the file and its default keys
+const ACTIONS: [(&str, Action, &[&str]); 10] = [
+	("move_left", Action::Game(GameInput::MoveLeft), &["Left", "a"]),
+	("move_right", Action::Game(GameInput::MoveRight), &["Right", "d"]),
+	("soft_drop", Action::Game(GameInput::MoveDown), &["Down", "s"]),
+	("hard_drop", Action::Game(GameInput::DropDown), &["Space"]),
+	("rotate_clockwise", Action::Game(GameInput::SpinLeft), &["Up", "w"]),
+	("rotate_counter_clockwise", Action::Game(GameInput::SpinRight), &["q", "z"]),
+	("rotate_180", Action::Game(GameInput::Spin180), &["e"]),
+	("hold", Action::Game(GameInput::Hold), &["c"]),
+	("pause", Action::Pause, &["p", "Esc"]),
+	("quit", Action::Quit, &["x"]),
+];
+
+// the default keys of each player in versus, which can't overlap
+const VERSUS_ACTIONS: [[(&str, Action, &[&str]); 10]; 2] = [
+	[
+		("move_left", Action::Game(GameInput::MoveLeft), &["a"]),
+		("move_right", Action::Game(GameInput::MoveRight), &["d"]),
+		("soft_drop", Action::Game(GameInput::MoveDown), &["s"]),
+		("hard_drop", Action::Game(GameInput::DropDown), &["Space"]),
+		("rotate_clockwise", Action::Game(GameInput::SpinLeft), &["w"]),
+		("rotate_counter_clockwise", Action::Game(GameInput::SpinRight), &["q"]),
+		("rotate_180", Action::Game(GameInput::Spin180), &["e"]),
+		("hold", Action::Game(GameInput::Hold), &["c"]),
+		("pause", Action::Pause, &["p", "Esc"]),
+		("quit", Action::Quit, &["x"]),
+	],
+	[
+		("move_left", Action::Game(GameInput::MoveLeft), &["Left"]),
+		("move_right", Action::Game(GameInput::MoveRight), &["Right"]),
+		("soft_drop", Action::Game(GameInput::MoveDown), &["Down"]),
+		("hard_drop", Action::Game(GameInput::DropDown), &["Enter"]),
+		("rotate_clockwise", Action::Game(GameInput::SpinLeft), &["Up"]),
+		("rotate_counter_clockwise", Action::Game(GameInput::SpinRight), &["/"]),
+		("rotate_180", Action::Game(GameInput::Spin180), &["."]),
+		("hold", Action::Game(GameInput::Hold), &[","]),
+		("pause", Action::Pause, &[]),
+		("quit", Action::Quit, &[]),
+	],
+];
+
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+	actions: HashMap<Key, Action>,
+}
+
+impl Default for KeyBindings {
+	fn default() -> Self {
+		Self::from_toml("").expect("The default key bindings must be valid")
+	}
+}
+
+impl KeyBindings {
+	// where the key bindings are read from when no file is given
+	pub fn default_path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join("tetris-rs").join("keys.toml"))
+	}
+
+	// reads the key bindings from a file, or from the default path if it exists
+	pub fn load(path: Option<&Path>) -> Result<Self, String> {
+		let path = match path {
+			Some(path) => path.to_path_buf(),
+			None => match Self::default_path().filter(|path| path.exists()) {
+				Some(path) => path,
+				None => return Ok(Self::default()),
+			}
+		};
+		let text = std::fs::read_to_string(&path)
+			.map_err(|e| format!("Can't read the key bindings from {}: {}", path.display(), e))?;
+		Self::from_toml(&text)
+			.map_err(|e| format!("Invalid key bindings in {}:\n{}", path.display(), e))
+	}
+
+	// parses key bindings, reporting every unknown action, unknown key and key bound to more than one action
+	pub fn from_toml(text: &str) -> Result<Self, String> {
+		let table: toml::Table = text.parse().map_err(|e| format!("{}", e))?;
+		Self::from_table(&table, &ACTIONS).map_err(|errors| errors.join("\n"))
+	}
+
+	// where the key bindings of versus are read from when no file is given
+	pub fn versus_path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join("tetris-rs").join("versus-keys.toml"))
+	}
+
+	// reads the key bindings of both players in versus from a file, or from the versus path if it exists
+	pub fn load_versus(path: Option<&Path>) -> Result<[Self; 2], String> {
+		let path = match path {
+			Some(path) => Some(path.to_path_buf()),
+			None => Self::versus_path().filter(|path| path.exists()),
+		};
+		let text = match &path {
+			Some(path) => std::fs::read_to_string(path)
+				.map_err(|e| format!("Can't read the key bindings from {}: {}", path.display(), e))?,
+			None => String::new(),
+		};
+		let name = path.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "the default versus keys".to_string());
+		Self::from_versus_toml(&text).map_err(|e| format!("Invalid key bindings in {}:\n{}", name, e))
+	}
+
+	// parses the tables of both players, reporting keys that both players are bound to as well
+	pub fn from_versus_toml(text: &str) -> Result<[Self; 2], String> {
+		const PLAYERS: [&str; 2] = ["player1", "player2"];
+
+		let table: toml::Table = text.parse().map_err(|e| format!("{}", e))?;
+		let mut errors = Vec::new();
+		for name in table.keys() {
+			if !PLAYERS.contains(&name.as_str()) {
+				errors.push(format!("Unknown table '{}'. Try player1 or player2", name));
+			}
+		}
+
+		let mut players = Vec::new();
+		for (name, actions) in PLAYERS.iter().zip(&VERSUS_ACTIONS) {
+			let player = match table.get(*name) {
+				None => Self::from_table(&toml::Table::new(), actions),
+				Some(toml::Value::Table(player)) => Self::from_table(player, actions),
+				Some(_) => {
+					errors.push(format!("'{}' must be a table", name));
+					continue;
+				}
+			};
+			match player {
+				Ok(player) => players.push(player),
+				Err(player_errors) => errors.extend(player_errors.into_iter().map(|e| format!("{}: {}", name, e))),
+			}
+		}
+		if let [first, second] = &players[..] {
+			for key in first.actions.keys().filter(|key| second.actions.contains_key(key)) {
+				errors.push(format!("'{}' is bound for both players", key));
+			}
+		}
+
+		match players.try_into() {
+			Ok(players) if errors.is_empty() => Ok(players),
+			_ => Err(errors.join("\n")),
+		}
+	}
+
+	fn from_table(table: &toml::Table, default_actions: &[(&str, Action, &[&str])]) -> Result<Self, Vec<String>> {
+		let mut errors = Vec::new();
+
+		for name in table.keys() {
+			if !default_actions.iter().any(|(action_name, _, _)| action_name == name) {
+				errors.push(format!("Unknown action '{}'", name));
+			}
+		}
+
+		let mut actions = HashMap::new();
+		for &(name, action, default_keys) in default_actions {
+			let key_names: Vec<&str> = match table.get(name) {
+				None => default_keys.to_vec(),
+				Some(toml::Value::String(key)) => vec![key.as_str()],
+				Some(toml::Value::Array(keys)) => keys.iter().filter_map(|key| {
+					let key = key.as_str();
+					if key.is_none() {
+						errors.push(format!("The keys of '{}' must be strings", name));
+					}
+					key
+				}).collect(),
+				Some(_) => {
+					errors.push(format!("'{}' must be a key or a list of keys", name));
+					continue;
+				}
+			};
+
+			for key_name in key_names {
+				let Ok(key) = key_name.parse::<Key>() else {
+					errors.push(format!("Unknown key '{}' for '{}'", key_name, name));
+					continue;
+				};
+				if let Some(other) = actions.insert(normalize(key), action) {
+					if other != action {
+						let other_name = default_actions.iter().find(|(_, a, _)| *a == other).unwrap().0;
+						errors.push(format!("'{}' is bound to both '{}' and '{}'", key, other_name, name));
+					}
+				}
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(Self { actions })
+		} else {
+			Err(errors)
+		}
+	}
+
+	// a shifted letter without a binding of its own does what the letter does, so caps lock doesn't get in the way
+	pub fn action(&self, key: Key) -> Option<Action> {
+		let key = normalize(key);
+		self.actions.get(&key).copied().or_else(|| {
+			let mut unshifted = key;
+			unshifted.modifiers.shift = false;
+			match key.code {
+				KeyCode::Char(_) if key.modifiers.shift => self.actions.get(&unshifted).copied(),
+				_ => None,
+			}
+		})
+	}
+}
+
+// letters are kept lowercase, with a capital taken as shift+letter, since terminals with the kitty keyboard protocol
+// send shift+a where others send A. so Shift+a and A are the same key, and a different one than a
+fn normalize(mut key: Key) -> Key {
+	if let KeyCode::Char(c) = key.code {
+		key.modifiers.shift |= c.is_ascii_uppercase();
+		key.code = KeyCode::Char(c.to_ascii_lowercase());
+	}
+	key
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn action(bindings: &KeyBindings, key: &str) -> Option<Action> {
+		bindings.action(key.parse().unwrap())
+	}
+
+	#[test]
+	fn missing_actions_keep_their_default_keys() {
+		let bindings = KeyBindings::from_toml("move_left = [\"j\", \"Ctrl+Left\"]\nhold = \"Shift+Up\"").unwrap();
+		assert_eq!(action(&bindings, "j"), Some(Action::Game(GameInput::MoveLeft)));
+		assert_eq!(action(&bindings, "Ctrl+Left"), Some(Action::Game(GameInput::MoveLeft)));
+		assert_eq!(action(&bindings, "Shift+Up"), Some(Action::Game(GameInput::Hold)));
+		// the keys given replace the defaults of the action
+		assert_eq!(action(&bindings, "a"), None);
+		assert_eq!(action(&bindings, "c"), None);
+		assert_eq!(action(&bindings, "d"), Some(Action::Game(GameInput::MoveRight)));
+		assert_eq!(action(&bindings, "Esc"), Some(Action::Pause));
+	}
+
+	#[test]
+	fn every_bad_binding_is_reported() {
+		let errors = KeyBindings::from_toml(concat!(
+			"jump = \"j\"\n",
+			"move_left = \"Hyper+a\"\n",
+			"move_right = \"c\"\n",
+			"soft_drop = 2\n",
+			"hard_drop = [\"Space\", 3]\n",
+		)).unwrap_err();
+		assert_eq!(errors.lines().collect::<Vec<_>>(), [
+			"Unknown action 'jump'",
+			"Unknown key 'Hyper+a' for 'move_left'",
+			"'soft_drop' must be a key or a list of keys",
+			"The keys of 'hard_drop' must be strings",
+			"'c' is bound to both 'move_right' and 'hold'",
+		]);
+		assert!(KeyBindings::from_toml("move_left = ").is_err());
+	}
+
+	#[test]
+	fn loading_a_file_says_which_one_is_wrong() {
+		let path = std::env::temp_dir().join(format!("tetris-keys-{}.toml", std::process::id()));
+		std::fs::write(&path, "quit = \"F12\"").unwrap();
+		let bindings = KeyBindings::load(Some(&path)).unwrap();
+		assert_eq!(action(&bindings, "F12"), Some(Action::Quit));
+
+		std::fs::write(&path, "quit = \"Nope\"").unwrap();
+		let error = KeyBindings::load(Some(&path)).unwrap_err();
+		assert_eq!(error, format!("Invalid key bindings in {}:\nUnknown key 'Nope' for 'quit'", path.display()));
+		std::fs::remove_file(&path).unwrap();
+		assert!(KeyBindings::load(Some(&path)).unwrap_err().starts_with("Can't read the key bindings from"));
+	}
+
+	#[test]
+	fn versus_players_have_their_own_keys() {
+		let [first, second] = KeyBindings::from_versus_toml("").unwrap();
+		assert_eq!(action(&first, "a"), Some(Action::Game(GameInput::MoveLeft)));
+		assert_eq!(action(&first, "Left"), None);
+		assert_eq!(action(&second, "Left"), Some(Action::Game(GameInput::MoveLeft)));
+		assert_eq!(action(&second, "a"), None);
+		assert_eq!(action(&second, "p"), None);
+
+		let [first, _] = KeyBindings::from_versus_toml("[player1]\nhold = \"Tab\"").unwrap();
+		assert_eq!(action(&first, "Tab"), Some(Action::Game(GameInput::Hold)));
+	}
+
+	#[test]
+	fn versus_reports_bad_tables_and_shared_keys() {
+		let errors = KeyBindings::from_versus_toml("player3 = 1\nplayer1 = 2").unwrap_err();
+		assert_eq!(errors.lines().collect::<Vec<_>>(), [
+			"Unknown table 'player3'. Try player1 or player2",
+			"'player1' must be a table",
+		]);
+		let errors = KeyBindings::from_versus_toml("[player1]\njump = \"j\"").unwrap_err();
+		assert_eq!(errors, "player1: Unknown action 'jump'");
+		let errors = KeyBindings::from_versus_toml("[player2]\nhold = \"c\"").unwrap_err();
+		assert_eq!(errors, "'c' is bound for both players");
+	}
+
+	#[test]
+	fn shifted_letters_fall_back_to_the_letter() {
+		let bindings = KeyBindings::default();
+		assert_eq!(action(&bindings, "A"), Some(Action::Game(GameInput::MoveLeft)));
+		assert_eq!(action(&bindings, "Shift+a"), Some(Action::Game(GameInput::MoveLeft)));
+		// only letters, other keys with shift are keys of their own
+		assert_eq!(action(&bindings, "Shift+Left"), None);
+
+		// A and Shift+a are one key, apart from a
+		let bindings = KeyBindings::from_toml("hold = \"A\"").unwrap();
+		assert_eq!(action(&bindings, "Shift+a"), Some(Action::Game(GameInput::Hold)));
+		assert_eq!(action(&bindings, "a"), Some(Action::Game(GameInput::MoveLeft)));
+		let errors = KeyBindings::from_toml("hold = \"A\"\nrotate_180 = \"Shift+a\"").unwrap_err();
+		assert!(errors.contains("is bound to both 'rotate_180' and 'hold'"), "{}", errors);
+	}
+}
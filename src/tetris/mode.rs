@@ -0,0 +1,94 @@
+// What a game is played for: when it's won or over, and what's shown about it under the board
+
+use super::engine::Engine;
+
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+	Marathon { lines: u32 }, // clear the lines, with the level going up on the way
+	#[default]
+	Endless, // marathon without a goal, until the stack tops out
+	Sprint { lines: u32 }, // clear the lines as fast as possible
+	Ultra { time: Duration }, // score as much as possible before the time runs out
+	Zen, // no goal and no game over: rows clear from the bottom when the stack reaches the top
+	Dig { rows: u32, messiness: u32 }, // clear the rows of garbage the board starts with as fast as possible
+}
+
+// the most rows of garbage on the board at once in dig mode, the rest come up as they're cleared
+pub const DIG_VISIBLE_ROWS: u32 = 10;
+
+impl GameMode {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"marathon" => Some(Self::Marathon { lines: 150 }),
+			"endless" => Some(Self::Endless),
+			"sprint" | "40l" => Some(Self::Sprint { lines: 40 }),
+			"ultra" => Some(Self::Ultra { time: Duration::from_secs(120) }),
+			"zen" => Some(Self::Zen),
+			"dig" | "cheese" => Some(Self::Dig { rows: 10, messiness: 100 }),
+			_ => None,
+		}
+	}
+
+	// the lines that win the game, if any
+	pub fn line_goal(&self) -> Option<u32> {
+		match self {
+			Self::Marathon { lines } | Self::Sprint { lines } => Some(*lines),
+			_ => None,
+		}
+	}
+
+	// the rows of garbage to dig through, if any
+	pub fn dig_rows(&self) -> Option<u32> {
+		match self {
+			Self::Dig { rows, .. } => Some(*rows),
+			_ => None,
+		}
+	}
+
+	// the chance in percent that the hole of a garbage row moves from the row below
+	pub fn messiness(&self) -> u32 {
+		match self {
+			Self::Dig { messiness, .. } => *messiness,
+			_ => 100,
+		}
+	}
+
+	// how long the game lasts, if it's timed
+	pub fn time_limit(&self) -> Option<Duration> {
+		match self {
+			Self::Ultra { time } => Some(*time),
+			_ => None,
+		}
+	}
+
+	// whether the HUD has a clock on it
+	pub fn shows_time(&self) -> bool {
+		matches!(self, Self::Sprint { .. } | Self::Ultra { .. } | Self::Dig { .. })
+	}
+
+	// whether the game ends when the stack reaches the top
+	pub fn can_top_out(&self) -> bool {
+		*self != Self::Zen
+	}
+
+	// the lines shown under the score
+	pub fn hud(&self, engine: &Engine) -> Vec<String> {
+		match self {
+			Self::Marathon { lines } => vec![format!("Marathon  Level {} Lines {}/{}", engine.level(), engine.lines(), lines)],
+			Self::Endless => vec![format!("Level {} Lines {}", engine.level(), engine.lines())],
+			Self::Sprint { lines } => vec![format!("Sprint  Lines {}/{} Time {}", engine.lines(), lines, format_time(engine.time()))],
+			Self::Ultra { time } => vec![format!("Ultra  Lines {} Time left {}", engine.lines(), format_time(time.saturating_sub(engine.time())))],
+			Self::Zen => vec![format!("Zen  Lines {}", engine.lines())],
+			Self::Dig { .. } => vec![format!("Dig  Garbage left {} Time {}", engine.garbage_rows(), format_time(engine.time()))],
+		}
+	}
+}
+
+// minutes, seconds and hundredths, like 1:02.35
+pub fn format_time(time: Duration) -> String {
+	let hundredths = time.as_millis() / 10;
+	format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
@@ -0,0 +1,322 @@
+// Versus over the network: every player runs their own engine and tells the others what happens on their board
+// through a server, one JSON message per line over TCP. The server picks the game both players play, passes the
+// garbage each one sends to the other, and passes the boards on to anyone watching
+
+use super::engine::{GameEvent, Outcome, Snapshot};
+use super::randomizer::{self, Randomizer};
+use super::scoring::{self, Scoring};
+use super::settings::Settings;
+use super::GameInput;
+
+use serde::{Serialize, Deserialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// both ends have to speak the same version, which they tell each other first
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+// how long a new connection has to say hello before it's dropped
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+// how often the server looks for messages from the players
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Serialize, Deserialize)]
+pub enum Message {
+	Hello { version: u32 }, // the first message both ways
+	Watch { version: u32 }, // sent instead of hello by someone who only watches
+	Welcome(GameSetup), // the game starts, sent by the server once two players are in
+	Event { player: usize, event: GameEvent }, // pieces spawning, locking and clearing lines
+	Inputs { player: usize, inputs: Vec<(u64, GameInput)> }, // with the tick they were applied on
+	Garbage { rows: u32 }, // sent to the server as an attack, and passed on to the opponent as incoming garbage
+	Board { player: usize, snapshot: Box<Snapshot> }, // the whole board, to draw it on the other end
+	// the player's game ended, which ends the match. when the time ran out, it ends once both players' did, by score
+	GameOver { player: usize, outcome: Outcome, score: u32 },
+	Error { message: String }, // why the game can't go on, before the connection is closed
+}
+
+impl Message {
+	// the message with the player it came from, which the server sets rather than trusting the client
+	fn with_player(mut self, index: usize) -> Self {
+		match &mut self {
+			Self::Event { player, .. } | Self::Inputs { player, .. } | Self::Board { player, .. } | Self::GameOver { player, .. } => *player = index,
+			_ => (),
+		}
+		self
+	}
+}
+
+// the game the server has both players play, so they get the same pieces
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameSetup {
+	pub player: Option<usize>, // the index of the player it's sent to, none for someone watching
+	pub seed: u64,
+	pub randomizer: String,
+	pub scoring: String,
+	pub settings: Settings,
+}
+
+impl GameSetup {
+	pub fn make_randomizer(&self) -> Result<Box<dyn Randomizer>, String> {
+		randomizer::from_name(&self.randomizer).ok_or_else(|| format!("Unknown randomizer '{}' from the server", self.randomizer))
+	}
+
+	pub fn make_scoring(&self) -> Result<Box<dyn Scoring>, String> {
+		scoring::from_name(&self.scoring).ok_or_else(|| format!("Unknown scoring '{}' from the server", self.scoring))
+	}
+}
+
+// one end of a connection. messages are read on a thread of their own, so they can be picked up without waiting
+pub struct Connection {
+	stream: TcpStream,
+	incoming: mpsc::Receiver<Result<Message, String>>,
+}
+
+impl Connection {
+	pub fn new(stream: TcpStream) -> Result<Self, String> {
+		// the messages are small and need to get there right away
+		stream.set_nodelay(true).map_err(|e| format!("Can't set up the connection: {}", e))?;
+		let reader = stream.try_clone().map_err(|e| format!("Can't set up the connection: {}", e))?;
+		let (sender, incoming) = mpsc::channel();
+		thread::spawn(move || {
+			for line in BufReader::new(reader).lines() {
+				let message = match line {
+					Ok(line) => serde_json::from_str(&line).map_err(|e| format!("Got a message that isn't understood: {}", e)),
+					Err(e) => Err(format!("The connection was lost: {}", e)),
+				};
+				let failed = message.is_err();
+				if sender.send(message).is_err() || failed {
+					return;
+				}
+			}
+			let _ = sender.send(Err("The connection was closed".to_string()));
+		});
+		Ok(Self { stream, incoming })
+	}
+
+	pub fn connect(address: &str) -> Result<Self, String> {
+		let stream = TcpStream::connect(address).map_err(|e| format!("Can't connect to {}: {}", address, e))?;
+		Self::new(stream)
+	}
+
+	pub fn send(&mut self, message: &Message) -> Result<(), String> {
+		let mut line = serde_json::to_string(message).expect("A message can always be serialized");
+		line.push('\n');
+		self.stream.write_all(line.as_bytes()).map_err(|e| format!("The connection was lost: {}", e))
+	}
+
+	// the next message if there's one, without waiting for it
+	pub fn try_recv(&self) -> Option<Result<Message, String>> {
+		match self.incoming.try_recv() {
+			Ok(message) => Some(message),
+			Err(mpsc::TryRecvError::Empty) => None,
+			Err(mpsc::TryRecvError::Disconnected) => Some(Err("The connection was closed".to_string())),
+		}
+	}
+
+	pub fn recv(&self) -> Result<Message, String> {
+		self.incoming.recv().unwrap_or_else(|_| Err("The connection was closed".to_string()))
+	}
+
+	pub fn recv_timeout(&self, timeout: Duration) -> Result<Message, String> {
+		match self.incoming.recv_timeout(timeout) {
+			Ok(message) => message,
+			Err(mpsc::RecvTimeoutError::Timeout) => Err("Timed out waiting for a message".to_string()),
+			Err(mpsc::RecvTimeoutError::Disconnected) => Err("The connection was closed".to_string()),
+		}
+	}
+}
+
+impl Drop for Connection {
+	// the reading thread holds a copy of the socket, so it's shut down for the other end to see it close.
+	// only the writing half, so what was sent last still gets there; the reading thread ends when the other end closes too
+	fn drop(&mut self) {
+		let _ = self.stream.shutdown(Shutdown::Write);
+	}
+}
+
+// connects to a server and waits there until an opponent joins and the game starts
+pub fn join(address: &str) -> Result<(Connection, GameSetup), String> {
+	enter(address, Message::Hello { version: PROTOCOL_VERSION })
+}
+
+// connects to a server to watch the game that started last, or the next one if none is on
+pub fn watch(address: &str) -> Result<(Connection, GameSetup), String> {
+	enter(address, Message::Watch { version: PROTOCOL_VERSION })
+}
+
+fn enter(address: &str, hello: Message) -> Result<(Connection, GameSetup), String> {
+	let mut connection = Connection::connect(address)?;
+	connection.send(&hello)?;
+	match connection.recv_timeout(HELLO_TIMEOUT)? {
+		Message::Hello { version } if version == PROTOCOL_VERSION => (),
+		Message::Hello { version } => {
+			return Err(format!("The server speaks version {} of the protocol, this game speaks version {}", version, PROTOCOL_VERSION));
+		}
+		Message::Error { message } => return Err(message),
+		_ => return Err(format!("{} didn't answer like a tetris server", address)),
+	}
+	loop {
+		match connection.recv()? {
+			Message::Welcome(setup) => return Ok((connection, setup)),
+			Message::Error { message } => return Err(message),
+			_ => (),
+		}
+	}
+}
+
+// pairs up the first two clients that connect for a game, then the next two, and so on.
+// every game runs on a thread of its own, so a pair doesn't wait for the game before theirs to end
+pub struct Server {
+	listener: TcpListener,
+	seed: Option<u64>, // a new one for every game if none
+	randomizer: String,
+	scoring: String,
+	settings: Settings,
+}
+
+// a connection that said hello, to play or to watch
+enum Joined {
+	Player(Connection),
+	Spectator(Connection),
+}
+
+impl Server {
+	pub fn bind(address: &str, seed: Option<u64>, randomizer: String, scoring: String, settings: Settings) -> Result<Self, String> {
+		let listener = TcpListener::bind(address).map_err(|e| format!("Can't listen on {}: {}", address, e))?;
+		Ok(Self { listener, seed, randomizer, scoring, settings })
+	}
+
+	// the address it listens on, which has the port picked for it when bound to port 0
+	pub fn local_addr(&self) -> Result<SocketAddr, String> {
+		self.listener.local_addr().map_err(|e| e.to_string())
+	}
+
+	// starts games until the listener fails
+	pub fn serve(&self) -> Result<(), String> {
+		let listener = self.listener.try_clone().map_err(|e| e.to_string())?;
+		let (sender, connections) = mpsc::channel();
+		// the handshakes happen on threads of their own, so a slow client doesn't hold up the others
+		thread::spawn(move || {
+			for stream in listener.incoming().flatten() {
+				let sender = sender.clone();
+				thread::spawn(move || {
+					if let Some(joined) = handshake(stream) {
+						let _ = sender.send(joined);
+					}
+				});
+			}
+		});
+
+		let mut waiting: Vec<Connection> = Vec::new();
+		let mut spectators: Vec<Connection> = Vec::new(); // the ones waiting for a game to watch
+		// the games that are on, oldest first, with where to send someone who wants to watch them
+		let mut games: Vec<(thread::JoinHandle<()>, mpsc::Sender<Connection>)> = Vec::new();
+		loop {
+			// anyone who left while waiting is dropped before the game starts
+			waiting.retain(|player| !matches!(player.try_recv(), Some(Err(_))));
+			match connections.recv_timeout(Duration::from_millis(100)) {
+				Ok(Joined::Player(connection)) => waiting.push(connection),
+				Ok(Joined::Spectator(connection)) => spectators.push(connection),
+				Err(mpsc::RecvTimeoutError::Timeout) => (),
+				Err(mpsc::RecvTimeoutError::Disconnected) => return Err("The server stopped taking connections".to_string()),
+			}
+			games.retain(|(game, _)| !game.is_finished());
+			if waiting.len() >= 2 {
+				let players: Vec<Connection> = waiting.drain(..2).collect();
+				let setup = self.setup();
+				let (sender, spectators) = mpsc::channel();
+				games.push((thread::spawn(move || play_game(players, setup, spectators)), sender));
+			}
+			if let Some((_, game)) = games.last() {
+				for spectator in spectators.drain(..) {
+					// a game that just ended gives the connection back, and it's dropped
+					let _ = game.send(spectator);
+				}
+			}
+		}
+	}
+
+	// runs the server on a thread of its own, for a game on this machine or a test
+	pub fn spawn(self) -> thread::JoinHandle<Result<(), String>> {
+		thread::spawn(move || self.serve())
+	}
+
+	fn setup(&self) -> GameSetup {
+		GameSetup {
+			player: None,
+			seed: self.seed.unwrap_or_else(rand::random),
+			randomizer: self.randomizer.clone(),
+			scoring: self.scoring.clone(),
+			settings: self.settings.clone(),
+		}
+	}
+}
+
+// starts the game for two players, then passes on what they send until one of their games ends or one of them leaves
+fn play_game(mut players: Vec<Connection>, setup: GameSetup, joining: mpsc::Receiver<Connection>) {
+	for (index, player) in players.iter_mut().enumerate() {
+		let _ = player.send(&Message::Welcome(GameSetup { player: Some(index), ..setup.clone() }));
+	}
+
+	let mut spectators: Vec<Connection> = Vec::new();
+	let mut time_up = [false; 2];
+	loop {
+		for mut connection in joining.try_iter() {
+			if connection.send(&Message::Welcome(setup.clone())).is_ok() {
+				spectators.push(connection);
+			}
+		}
+		for index in 0..players.len() {
+			let opponent = 1 - index;
+			while let Some(message) = players[index].try_recv() {
+				let message = match message {
+					// garbage only goes to the opponent
+					Ok(Message::Garbage { rows }) => {
+						let _ = players[opponent].send(&Message::Garbage { rows });
+						continue;
+					}
+					Ok(message @ (Message::Event { .. } | Message::Inputs { .. } | Message::Board { .. } | Message::GameOver { .. })) => {
+						message.with_player(index)
+					}
+					Ok(_) => continue,
+					Err(_) => Message::Error { message: format!("Player {} left the game", index + 1) },
+				};
+				let _ = players[opponent].send(&message);
+				spectators.retain_mut(|spectator| spectator.send(&message).is_ok());
+				let ended = match &message {
+					Message::GameOver { outcome, .. } if outcome.is_time_up() => {
+						time_up[index] = true;
+						time_up.iter().all(|&up| up)
+					}
+					Message::GameOver { .. } | Message::Error { .. } => true,
+					_ => false,
+				};
+				if ended {
+					return;
+				}
+			}
+		}
+		thread::sleep(POLL_INTERVAL);
+	}
+}
+
+// checks that a new connection speaks the same version, and whether it plays or watches
+fn handshake(stream: TcpStream) -> Option<Joined> {
+	let mut connection = Connection::new(stream).ok()?;
+	let (version, joined): (u32, fn(Connection) -> Joined) = match connection.recv_timeout(HELLO_TIMEOUT) {
+		Ok(Message::Hello { version }) => (version, Joined::Player),
+		Ok(Message::Watch { version }) => (version, Joined::Spectator),
+		_ => return None,
+	};
+	if version != PROTOCOL_VERSION {
+		let message = format!("The server speaks version {} of the protocol, this game speaks version {}", PROTOCOL_VERSION, version);
+		let _ = connection.send(&Message::Error { message });
+		return None;
+	}
+	connection.send(&Message::Hello { version: PROTOCOL_VERSION }).ok()?;
+	Some(joined(connection))
+}
@@ -0,0 +1,188 @@
+// Decides which tetromino comes next
+
+use super::tetromino::Shape;
+use super::save;
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Serialize, Deserialize};
+
+pub trait Randomizer {
+	// returns the shape of the next piece, using rng as the source of randomness
+	fn next_shape(&mut self, rng: &mut dyn RngCore) -> Shape;
+
+	// forgets the pieces dealt so far, so a new game with the same rng gets the same pieces
+	fn reset(&mut self);
+
+	// the name from_name creates it by
+	fn name(&self) -> String;
+
+	// the pieces dealt so far, to save a game in progress
+	fn save(&self) -> serde_json::Value;
+
+	// picks up from what save returned
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String>;
+}
+
+// creates a randomizer by its name, as given on the command line
+pub fn from_name(name: &str) -> Option<Box<dyn Randomizer>> {
+	match name {
+		"7bag" | "7-bag" | "bag" => Some(Box::new(Bag::new(1))),
+		"14bag" | "14-bag" => Some(Box::new(Bag::new(2))),
+		"uniform" | "random" => Some(Box::new(Uniform)),
+		"nes" | "classic" => Some(Box::new(Nes::default())),
+		_ => None,
+	}
+}
+
+// deals every shape a fixed number of times in a shuffled order, then refills.
+// one copy of each shape is the guideline 7-bag, two copies make a 14-bag
+#[derive(Serialize, Deserialize)]
+pub struct Bag {
+	copies: usize,
+	shapes: Vec<Shape>,
+}
+
+impl Bag {
+	pub fn new(copies: usize) -> Self {
+		Self {
+			copies: copies.max(1),
+			shapes: Vec::with_capacity(copies.max(1) * Shape::ALL.len()),
+		}
+	}
+}
+
+impl Default for Bag {
+	fn default() -> Self {
+		Self::new(1)
+	}
+}
+
+impl Randomizer for Bag {
+	fn next_shape(&mut self, mut rng: &mut dyn RngCore) -> Shape {
+		if self.shapes.is_empty() {
+			for _ in 0..self.copies {
+				self.shapes.extend_from_slice(&Shape::ALL);
+			}
+			self.shapes.shuffle(&mut rng);
+		}
+		self.shapes.pop().unwrap()
+	}
+
+	fn reset(&mut self) {
+		self.shapes.clear();
+	}
+
+	fn name(&self) -> String {
+		format!("{}bag", self.copies * Shape::ALL.len())
+	}
+
+	fn save(&self) -> serde_json::Value {
+		save::to_state(self)
+	}
+
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String> {
+		*self = save::from_state(state)?;
+		Ok(())
+	}
+}
+
+// every shape has the same chance, regardless of history
+#[derive(Serialize, Deserialize)]
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+	fn next_shape(&mut self, rng: &mut dyn RngCore) -> Shape {
+		rng.gen()
+	}
+
+	fn reset(&mut self) {}
+
+	fn name(&self) -> String {
+		"uniform".to_string()
+	}
+
+	fn save(&self) -> serde_json::Value {
+		save::to_state(self)
+	}
+
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String> {
+		*self = save::from_state(state)?;
+		Ok(())
+	}
+}
+
+// the NES randomizer: rolls one of 8 options, and rolls again once if it got the
+// 8th option or the same shape as the previous piece
+#[derive(Default, Serialize, Deserialize)]
+pub struct Nes {
+	previous: Option<Shape>,
+}
+
+impl Randomizer for Nes {
+	fn next_shape(&mut self, rng: &mut dyn RngCore) -> Shape {
+		let roll = rng.gen_range(0..=Shape::ALL.len());
+		let shape = match Shape::ALL.get(roll) {
+			Some(shape) if Some(*shape) != self.previous => *shape,
+			_ => rng.gen(),
+		};
+		self.previous = Some(shape);
+		shape
+	}
+
+	fn reset(&mut self) {
+		self.previous = None;
+	}
+
+	fn name(&self) -> String {
+		"nes".to_string()
+	}
+
+	fn save(&self) -> serde_json::Value {
+		save::to_state(self)
+	}
+
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String> {
+		*self = save::from_state(state)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	// the first 70 shapes dealt for the seed, the way the engine deals them
+	fn deal(randomizer: &mut dyn Randomizer, seed: u64) -> Vec<Shape> {
+		randomizer.reset();
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		(0..70).map(|_| randomizer.next_shape(&mut rng)).collect()
+	}
+
+	fn assert_deterministic(mut randomizer: Box<dyn Randomizer>) {
+		let dealt = deal(randomizer.as_mut(), 1);
+		assert_eq!(deal(randomizer.as_mut(), 1), dealt, "{} dealt other shapes after a reset", randomizer.name());
+		let mut other = from_name(&randomizer.name()).unwrap();
+		assert_eq!(deal(other.as_mut(), 1), dealt, "another {} dealt other shapes", randomizer.name());
+		assert_ne!(deal(other.as_mut(), 2), dealt, "{} dealt the same shapes for another seed", randomizer.name());
+	}
+
+	#[test]
+	fn same_seed_deals_the_same_shapes() {
+		assert_deterministic(Box::new(Bag::new(1)));
+		assert_deterministic(Box::new(Bag::new(2)));
+		assert_deterministic(Box::new(Nes::default()));
+	}
+
+	#[test]
+	fn bag_deals_every_shape_once_per_copy() {
+		for copies in [1, 2] {
+			let dealt = deal(&mut Bag::new(copies), 3);
+			for bag in dealt.chunks(copies * Shape::ALL.len()) {
+				for shape in Shape::ALL {
+					assert_eq!(bag.iter().filter(|&&dealt| dealt == shape).count(), copies);
+				}
+			}
+		}
+	}
+}
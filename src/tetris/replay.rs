@@ -0,0 +1,145 @@
+// A game recorded as its seed, settings and inputs. The engine plays the same game for the same inputs on the
+// same ticks, so that's all it takes to play it again and get the exact same board and score
+
+use super::engine::Engine;
+use super::randomizer::{self, Randomizer};
+use super::scoring::{self, Scoring};
+use super::settings::Settings;
+use super::GameInput;
+
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use std::time::Duration;
+
+const REPLAY_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+	version: u32,
+	pub seed: u64,
+	pub randomizer: String,
+	pub scoring: String,
+	pub settings: Settings,
+	inputs: Vec<(u64, GameInput)>, // the ticks since the previous input, and the input
+	pub ticks: u64, // how many ticks the game lasted
+}
+
+impl Replay {
+	// inputs are given with the tick they were applied on
+	pub fn new(seed: u64, randomizer: String, scoring: String, settings: Settings, inputs: &[(u64, GameInput)], ticks: u64) -> Self {
+		let mut previous = 0;
+		let inputs = inputs.iter().map(|&(tick, input)| {
+			let since = tick - previous;
+			previous = tick;
+			(since, input)
+		}).collect();
+		Self { version: REPLAY_VERSION, seed, randomizer, scoring, settings, inputs, ticks }
+	}
+
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let text = std::fs::read_to_string(path)
+			.map_err(|e| format!("Can't read the replay {}: {}", path.display(), e))?;
+		let replay: Self = serde_json::from_str(&text)
+			.map_err(|e| format!("{} isn't a valid replay: {}", path.display(), e))?;
+		if replay.version != REPLAY_VERSION {
+			return Err(format!("{} is a version {} replay, this game plays version {}", path.display(), replay.version, REPLAY_VERSION));
+		}
+		// checked here so that playing it back can't fail
+		replay.make_randomizer()?;
+		replay.make_scoring()?;
+		Ok(replay)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), String> {
+		let text = serde_json::to_string(self).expect("A replay can always be serialized");
+		std::fs::write(path, text).map_err(|e| format!("Can't write the replay {}: {}", path.display(), e))
+	}
+
+	pub fn make_randomizer(&self) -> Result<Box<dyn Randomizer>, String> {
+		randomizer::from_name(&self.randomizer).ok_or_else(|| format!("Unknown randomizer '{}' in the replay", self.randomizer))
+	}
+
+	pub fn make_scoring(&self) -> Result<Box<dyn Scoring>, String> {
+		scoring::from_name(&self.scoring).ok_or_else(|| format!("Unknown scoring '{}' in the replay", self.scoring))
+	}
+
+	pub fn playback(&self) -> Playback {
+		let mut tick = 0;
+		let inputs = self.inputs.iter().map(|&(since, input)| {
+			tick += since;
+			(tick, input)
+		}).collect();
+		Playback { inputs, next: 0, ticks: self.ticks }
+	}
+
+	// plays the whole game without waiting, and returns the engine as the game ended
+	pub fn play(&self) -> Result<Engine, String> {
+		let mut engine = Engine::new(self.make_randomizer()?, self.make_scoring()?, self.seed, self.settings.clone());
+		engine.reset();
+		let mut playback = self.playback();
+		for tick in 0..self.ticks {
+			engine.tick(&playback.inputs_at(tick));
+		}
+		engine.step(&playback.inputs_at(self.ticks), Duration::ZERO);
+		Ok(engine)
+	}
+}
+
+// the inputs of a replay, handed out tick by tick
+pub struct Playback {
+	inputs: Vec<(u64, GameInput)>, // with the tick they're applied on
+	next: usize,
+	ticks: u64,
+}
+
+impl Playback {
+	// the inputs applied on the tick, to be given to the engine before it runs the tick.
+	// ticks have to be asked for in order
+	pub fn inputs_at(&mut self, tick: u64) -> Vec<GameInput> {
+		let start = self.next;
+		while self.inputs.get(self.next).is_some_and(|&(at, _)| at <= tick) {
+			self.next += 1;
+		}
+		self.inputs[start..self.next].iter().map(|&(_, input)| input).collect()
+	}
+
+	// the tick the game ended on
+	pub fn ticks(&self) -> u64 {
+		self.ticks
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Move;
+	use super::super::engine::TICK;
+
+	#[test]
+	fn replay_plays_the_same_game() {
+		let mut engine = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 5, Settings::default());
+		engine.reset();
+		// steps of uneven lengths with a held move repeating across them. they're whole ticks, since what's left of
+		// a tick when the game ends isn't part of the replay
+		let inputs = [
+			vec![GameInput::Press(Move::Left)], vec![], vec![GameInput::Release(Move::Left), GameInput::SpinRight],
+			vec![GameInput::DropDown], vec![GameInput::Hold, GameInput::MoveRight], vec![GameInput::Spin180],
+			vec![GameInput::Press(Move::Down)], vec![], vec![GameInput::Release(Move::Down), GameInput::DropDown],
+			vec![GameInput::Press(Move::Right), GameInput::SpinLeft], vec![], vec![GameInput::DropDown],
+		];
+		for (index, inputs) in inputs.iter().cycle().take(120).enumerate() {
+			engine.step(inputs, TICK * (1 + index as u32 * 7 % 11));
+		}
+		assert!(engine.score() > 0);
+
+		let path = std::env::temp_dir().join(format!("tetris-replay-test-{}.json", std::process::id()));
+		engine.replay().save(&path).unwrap();
+		let loaded = Replay::load(&path);
+		std::fs::remove_file(&path).unwrap();
+		let played = loaded.unwrap().play().unwrap();
+
+		assert_eq!(played.score(), engine.score());
+		assert_eq!(played.ticks(), engine.ticks());
+		assert_eq!(serde_json::to_value(played.snapshot()).unwrap(), serde_json::to_value(engine.snapshot()).unwrap());
+	}
+}
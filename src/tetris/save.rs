@@ -0,0 +1,69 @@
+// A game in progress, saved when the player quits and picked up again on the next launch.
+// The file is the engine's snapshot with a version, so a save from another version of the game is refused
+// instead of being read wrong
+
+use super::engine::{Engine, Snapshot};
+use super::settings::{Settings, MIN_BUFFER_ROWS};
+
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::path::{Path, PathBuf};
+
+const SAVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+	version: u32,
+	snapshot: Snapshot,
+}
+
+impl SaveGame {
+	// where the game is saved when no file is given
+	pub fn default_path() -> Option<PathBuf> {
+		dirs::data_dir().map(|dir| dir.join("tetris-rs").join("save.json"))
+	}
+
+	// reads a saved game, which has to be played on a board of the same size as the settings
+	pub fn load(path: &Path, settings: &Settings) -> Result<Engine, String> {
+		let text = std::fs::read_to_string(path)
+			.map_err(|e| format!("Can't read the saved game {}: {}", path.display(), e))?;
+		// only the version is read first, since the rest of an older save may not parse
+		let version = serde_json::from_str::<serde_json::Value>(&text)
+			.ok()
+			.and_then(|save| save.get("version")?.as_u64());
+		if version != Some(SAVE_VERSION as u64) {
+			return Err(match version {
+				Some(version) => format!("{} is a version {} saved game, this game reads version {}. Move or delete it to start a new game",
+					path.display(), version, SAVE_VERSION),
+				None => format!("{} isn't a saved game. Move or delete it to start a new game", path.display()),
+			});
+		}
+		let save: Self = serde_json::from_str(&text)
+			.map_err(|e| format!("{} isn't a valid saved game: {}. Move or delete it to start a new game", path.display(), e))?;
+		let engine = Engine::from_snapshot(save.snapshot)
+			.map_err(|e| format!("{} isn't a valid saved game: {}. Move or delete it to start a new game", path.display(), e))?;
+		let buffer_rows = settings.buffer_rows.max(MIN_BUFFER_ROWS);
+		if (engine.width(), engine.height(), engine.buffer_rows()) != (settings.width, settings.height, buffer_rows) {
+			return Err(format!("{} was saved on a {}x{} board with {} buffer rows, this game plays on {}x{} with {}. Move or delete it to start a new game",
+				path.display(), engine.width(), engine.height(), engine.buffer_rows(), settings.width, settings.height, buffer_rows));
+		}
+		Ok(engine)
+	}
+
+	pub fn save(engine: &Engine, path: &Path) -> Result<(), String> {
+		let save = Self { version: SAVE_VERSION, snapshot: engine.snapshot() };
+		let text = serde_json::to_string(&save).expect("A saved game can always be serialized");
+		if let Some(dir) = path.parent() {
+			std::fs::create_dir_all(dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+		}
+		std::fs::write(path, text).map_err(|e| format!("Can't write the saved game {}: {}", path.display(), e))
+	}
+}
+
+// for randomizers and scorings to save their state with
+pub(crate) fn to_state<T: Serialize>(value: &T) -> serde_json::Value {
+	serde_json::to_value(value).expect("The state can always be serialized")
+}
+
+pub(crate) fn from_state<T: DeserializeOwned>(state: serde_json::Value) -> Result<T, String> {
+	serde_json::from_value(state).map_err(|e| e.to_string())
+}
@@ -0,0 +1,335 @@
+// Turns what happened when a piece locked into points
+
+use super::save;
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+// whether a lock counts as a T-spin
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Spin {
+	#[default]
+	None,
+	Mini,
+	Full,
+}
+
+// everything a scoring system needs to know about a piece that locked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Lock {
+	pub rows: u32, // rows cleared by the lock
+	pub spin: Spin,
+	pub perfect_clear: bool, // nothing is left on the board after clearing the rows
+	pub level: u32, // the level before the rows were cleared
+}
+
+// where the points of a single lock came from
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoreEvent {
+	pub rows: u32,
+	pub spin: Spin,
+	pub clear_points: u32, // for the clear itself, level multiplier included
+	pub back_to_back: bool,
+	pub back_to_back_bonus: u32,
+	pub combo: u32, // consecutive locks that cleared rows before this one, 0 if this isn't a combo
+	pub combo_bonus: u32,
+	pub perfect_clear: bool,
+	pub perfect_clear_bonus: u32,
+	pub total: u32,
+}
+
+impl ScoreEvent {
+	fn new(lock: Lock) -> Self {
+		Self {
+			rows: lock.rows,
+			spin: lock.spin,
+			perfect_clear: lock.perfect_clear,
+			..Default::default()
+		}
+	}
+
+	fn with_total(mut self) -> Self {
+		self.total = self.clear_points + self.back_to_back_bonus + self.combo_bonus + self.perfect_clear_bonus;
+		self
+	}
+}
+
+impl fmt::Display for ScoreEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts = Vec::new();
+		match self.spin {
+			Spin::None => {}
+			Spin::Mini => parts.push("T-Spin Mini"),
+			Spin::Full => parts.push("T-Spin"),
+		}
+		match self.rows {
+			0 => {}
+			1 => parts.push("Single"),
+			2 => parts.push("Double"),
+			3 => parts.push("Triple"),
+			_ => parts.push("Tetris"),
+		}
+		if self.back_to_back {
+			parts.push("B2B");
+		}
+		if self.perfect_clear {
+			parts.push("Perfect Clear");
+		}
+		write!(f, "{}", parts.join(" "))?;
+		if self.combo > 0 {
+			write!(f, " Combo {}", self.combo)?;
+		}
+		write!(f, " +{}", self.total)
+	}
+}
+
+pub trait Scoring {
+	// scores a piece that locked, after its rows were cleared
+	fn lock(&mut self, lock: Lock) -> ScoreEvent;
+
+	// points for moving the piece down by hand
+	fn soft_drop(&self, rows: u32) -> u32;
+
+	// points for dropping the piece all the way down
+	fn hard_drop(&self, rows: u32) -> u32;
+
+	// forgets combos and back-to-backs, for a new game
+	fn reset(&mut self);
+
+	// the name from_name creates it by
+	fn name(&self) -> String;
+
+	// the combos and back-to-backs so far, to save a game in progress
+	fn save(&self) -> serde_json::Value;
+
+	// picks up from what save returned
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String>;
+}
+
+// creates a scoring system by its name, as given on the command line
+pub fn from_name(name: &str) -> Option<Box<dyn Scoring>> {
+	match name {
+		"guideline" => Some(Box::<Guideline>::default()),
+		"nes" | "classic" => Some(Box::new(Nes)),
+		_ => None,
+	}
+}
+
+// the modern tetris guideline: T-spins, combos, back-to-backs and perfect clears, all times the level
+#[derive(Default, Serialize, Deserialize)]
+pub struct Guideline {
+	combo: Option<u32>, // None until a lock clears rows, then counts the locks in a row that did
+	back_to_back: bool, // the last clear was a tetris or a T-spin
+}
+
+impl Scoring for Guideline {
+	fn lock(&mut self, lock: Lock) -> ScoreEvent {
+		let level = lock.level.max(1);
+		let mut event = ScoreEvent::new(lock);
+
+		event.clear_points = level * match (lock.spin, lock.rows) {
+			(Spin::None, 0) => 0,
+			(Spin::None, 1) => 100,
+			(Spin::None, 2) => 300,
+			(Spin::None, 3) => 500,
+			(Spin::None, _) => 800,
+			(Spin::Mini, 0) => 100,
+			(Spin::Mini, 1) => 200,
+			(Spin::Mini, _) => 400,
+			(Spin::Full, 0) => 400,
+			(Spin::Full, 1) => 800,
+			(Spin::Full, 2) => 1200,
+			(Spin::Full, _) => 1600,
+		};
+
+		if lock.rows == 0 {
+			// a lock that clears nothing ends the combo, but keeps the back-to-back going
+			self.combo = None;
+			return event.with_total();
+		}
+
+		let difficult = lock.rows >= 4 || lock.spin != Spin::None;
+		event.back_to_back = difficult && self.back_to_back;
+		if event.back_to_back {
+			event.back_to_back_bonus = event.clear_points / 2;
+		}
+		self.back_to_back = difficult;
+
+		let combo = self.combo.map_or(0, |combo| combo + 1);
+		self.combo = Some(combo);
+		event.combo = combo;
+		event.combo_bonus = 50 * combo * level;
+
+		if lock.perfect_clear {
+			event.perfect_clear_bonus = level * match lock.rows {
+				1 => 800,
+				2 => 1200,
+				3 => 1800,
+				_ if event.back_to_back => 3200,
+				_ => 2000,
+			};
+		}
+		event.with_total()
+	}
+
+	fn soft_drop(&self, rows: u32) -> u32 {
+		rows
+	}
+
+	fn hard_drop(&self, rows: u32) -> u32 {
+		2 * rows
+	}
+
+	fn reset(&mut self) {
+		*self = Self::default();
+	}
+
+	fn name(&self) -> String {
+		"guideline".to_string()
+	}
+
+	fn save(&self) -> serde_json::Value {
+		save::to_state(self)
+	}
+
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String> {
+		*self = save::from_state(state)?;
+		Ok(())
+	}
+}
+
+// the NES: only the number of rows cleared counts, times the level plus one.
+// the NES starts at level 0, so the default start level of 1 scores twice what its first level does
+#[derive(Serialize, Deserialize)]
+pub struct Nes;
+
+impl Scoring for Nes {
+	fn lock(&mut self, lock: Lock) -> ScoreEvent {
+		let mut event = ScoreEvent::new(lock);
+		event.clear_points = (lock.level + 1) * match lock.rows {
+			0 => 0,
+			1 => 40,
+			2 => 100,
+			3 => 300,
+			_ => 1200,
+		};
+		event.with_total()
+	}
+
+	fn soft_drop(&self, rows: u32) -> u32 {
+		rows
+	}
+
+	fn hard_drop(&self, _rows: u32) -> u32 {
+		0
+	}
+
+	fn reset(&mut self) {}
+
+	fn name(&self) -> String {
+		"nes".to_string()
+	}
+
+	fn save(&self) -> serde_json::Value {
+		save::to_state(self)
+	}
+
+	fn restore(&mut self, state: serde_json::Value) -> Result<(), String> {
+		*self = save::from_state(state)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lock(rows: u32, spin: Spin, level: u32) -> Lock {
+		Lock { rows, spin, perfect_clear: false, level }
+	}
+
+	#[test]
+	fn guideline_table() {
+		let table = [
+			(0, Spin::None, 0), (1, Spin::None, 100), (2, Spin::None, 300), (3, Spin::None, 500), (4, Spin::None, 800),
+			(0, Spin::Mini, 100), (1, Spin::Mini, 200), (2, Spin::Mini, 400),
+			(0, Spin::Full, 400), (1, Spin::Full, 800), (2, Spin::Full, 1200), (3, Spin::Full, 1600),
+		];
+		for (rows, spin, points) in table {
+			for level in [1, 3] {
+				let event = Guideline::default().lock(lock(rows, spin, level));
+				assert_eq!(event.clear_points, points * level, "{} rows, {:?}, level {}", rows, spin, level);
+				assert_eq!(event.total, points * level);
+			}
+		}
+		// level 0 scores like level 1
+		assert_eq!(Guideline::default().lock(lock(1, Spin::None, 0)).total, 100);
+	}
+
+	#[test]
+	fn guideline_back_to_back() {
+		let mut scoring = Guideline::default();
+		let backs_to_back: Vec<(bool, u32)> = [
+			lock(4, Spin::None, 1),
+			lock(0, Spin::None, 1), // a lock without a clear doesn't break it
+			lock(2, Spin::Full, 1),
+			lock(1, Spin::None, 1), // a single does
+			lock(4, Spin::None, 1),
+		].into_iter().map(|lock| {
+			let event = scoring.lock(lock);
+			(event.back_to_back, event.back_to_back_bonus)
+		}).collect();
+		assert_eq!(backs_to_back, [(false, 0), (false, 0), (true, 600), (false, 0), (false, 0)]);
+	}
+
+	#[test]
+	fn guideline_combos() {
+		let mut scoring = Guideline::default();
+		let combos: Vec<(u32, u32)> = [
+			lock(1, Spin::None, 2), lock(1, Spin::None, 2), lock(2, Spin::None, 2),
+			lock(0, Spin::None, 2), // ends the combo
+			lock(1, Spin::None, 2),
+		].into_iter().map(|lock| {
+			let event = scoring.lock(lock);
+			(event.combo, event.combo_bonus)
+		}).collect();
+		assert_eq!(combos, [(0, 0), (1, 100), (2, 200), (0, 0), (0, 0)]);
+	}
+
+	#[test]
+	fn guideline_perfect_clears() {
+		let table = [(1, false, 800), (2, false, 1200), (3, false, 1800), (4, false, 2000), (4, true, 3200)];
+		for (rows, back_to_back, bonus) in table {
+			let mut scoring = Guideline::default();
+			if back_to_back {
+				scoring.lock(lock(4, Spin::None, 1));
+				scoring.lock(lock(0, Spin::None, 1));
+			}
+			let event = scoring.lock(Lock { perfect_clear: true, ..lock(rows, Spin::None, 1) });
+			assert_eq!(event.perfect_clear_bonus, bonus, "{} rows", rows);
+			assert_eq!(event.total, event.clear_points + event.back_to_back_bonus + bonus);
+		}
+	}
+
+	#[test]
+	fn score_event_explains_the_points() {
+		let mut scoring = Guideline::default();
+		assert_eq!(scoring.lock(lock(4, Spin::None, 1)).to_string(), "Tetris +800");
+		assert_eq!(scoring.lock(lock(2, Spin::Full, 1)).to_string(), "T-Spin Double B2B Combo 1 +1850");
+		assert_eq!(scoring.lock(lock(0, Spin::Mini, 1)).to_string(), "T-Spin Mini +100");
+		let event = scoring.lock(Lock { perfect_clear: true, ..lock(1, Spin::None, 1) });
+		assert_eq!(event.to_string(), "Single Perfect Clear +900");
+	}
+
+	#[test]
+	fn nes_table() {
+		let table = [(0, 0), (1, 40), (2, 100), (3, 300), (4, 1200)];
+		for (rows, points) in table {
+			// the level plus one, so level 0 is the NES's first level
+			assert_eq!(Nes.lock(lock(rows, Spin::None, 0)).total, points);
+			assert_eq!(Nes.lock(lock(rows, Spin::Full, 9)).total, points * 10);
+		}
+		assert_eq!(Nes.hard_drop(10), 0);
+		assert_eq!(Nes.soft_drop(10), 10);
+	}
+}
@@ -0,0 +1,69 @@
+// Options that change how a game of tetris plays, chosen before the game starts
+
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use super::gravity::GravityCurve;
+use super::mode::GameMode;
+
+pub const MAX_NEXT_QUEUE_LENGTH: usize = 6;
+pub const MAX_LOCK_RESETS: u32 = 15;
+// pieces spawn in the lowest two rows of the buffer above the board, so it can't be smaller
+pub const MIN_BUFFER_ROWS: usize = 2;
+// a soft drop factor of inf, which drops the faller all the way down while soft drop is held
+pub const INFINITE_SOFT_DROP_FACTOR: u32 = u32::MAX;
+
+// what restarts the lock delay of a piece resting on the stack
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockReset {
+	Move, // any successful move or rotation, up to MAX_LOCK_RESETS times per piece
+	Step, // only falling to a row lower than the piece has been before
+	None, // the piece locks when the delay runs out, no matter what
+}
+
+impl LockReset {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"move" => Some(Self::Move),
+			"step" => Some(Self::Step),
+			"none" => Some(Self::None),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+	pub width: usize,
+	pub height: usize, // the visible rows
+	pub buffer_rows: usize, // hidden rows above the visible ones, at least MIN_BUFFER_ROWS
+	pub next_queue_length: usize, // how many upcoming pieces are shown, 1 to MAX_NEXT_QUEUE_LENGTH
+	pub lock_delay: Duration, // how long a piece can rest on the stack before it locks
+	pub lock_reset: LockReset,
+	pub start_level: u32,
+	pub lines_per_level: u32, // the level goes up every time this many lines are cleared
+	pub gravity: GravityCurve,
+	pub das: Duration, // how long a sideways move is held before it starts repeating
+	pub arr: Duration, // the time between repeated sideways moves, 0 moves all the way at once
+	pub soft_drop_factor: u32, // how many times faster than gravity a soft drop falls
+	pub mode: GameMode,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			width: 10,
+			height: 20,
+			buffer_rows: MIN_BUFFER_ROWS,
+			next_queue_length: 5,
+			lock_delay: Duration::from_millis(500),
+			lock_reset: LockReset::Move,
+			start_level: 1,
+			lines_per_level: 10,
+			gravity: GravityCurve::Guideline,
+			das: Duration::from_millis(167),
+			arr: Duration::from_millis(33),
+			soft_drop_factor: 20,
+			mode: GameMode::default(),
+		}
+	}
+}
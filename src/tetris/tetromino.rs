@@ -1,20 +1,23 @@
 // Tetromino = shape composed of 4 cells connected orthogonally
 
-pub mod offsets;
-pub mod data;
+pub(crate) mod offsets;
+pub(crate) mod data;
 
-//use crate::tetris::v2::*;
+use crate::tetris::v2::V2;
 //use direction::*;
-use offsets::Offsets;
+use offsets::{Offsets, Kicks180, KICK_TESTS};
+use serde::{Serialize, Deserialize};
 use rand::{
 	distributions::{Distribution, Standard},
 	Rng
 };
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Shape { I, O, T, L, J, S, Z }
 
 impl Shape {
+	pub const ALL: [Shape; 7] = [Shape::I, Shape::O, Shape::T, Shape::L, Shape::J, Shape::S, Shape::Z];
+
 	pub fn fg_color_str(&self) -> &'static str {
 		use Shape::*;
 		use termion::color::*;
@@ -30,19 +33,10 @@ impl Shape {
 	}
 }
 
+// uniform over all shapes. Games draw their pieces through a Randomizer instead
 impl Distribution<Shape> for Standard {
 	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Shape {
-		use Shape::*;
-		match rng.gen_range(0..24) { // same as rand(6) + rand(6)?
-			0 => I,
-			1..=2 => O,
-			3..=5 => T,
-			6..=9 => L,
-			10..=13 => J,
-			14..=18 => S,
-			19..=23 => Z,
-			_ => panic!("Random number generated outisde what i wanted")
-		}
+		Shape::ALL[rng.gen_range(0..Shape::ALL.len())]
 	}
 }
 
@@ -74,6 +68,20 @@ impl Tetromino {
 		self.rotate(Side::Right);
 	}
 
+	pub fn rotate_180(&mut self) {
+		self.rotate(Side::Right);
+		self.rotate(Side::Right);
+	}
+
+	// the rotation state, 0 is the spawn rotation
+	pub fn rotation(&self) -> i8 {
+		self.state
+	}
+
+	pub fn set_rotation(&mut self, rotation: i8) {
+		self.state = rotation.rem_euclid(self.data.rotations.len() as i8);
+	}
+
 	// get current rotation
 	pub fn current_offsets(&self) -> &Offsets {
 		&self.data.rotations[self.state as usize]
@@ -84,6 +92,18 @@ impl Tetromino {
 		&self.data.directions[self.state as usize]
 	}
 
+	// get the translations to try, in order, when rotating from this state to the state of `rotated`
+	pub fn kicks_to(&self, rotated: &Self) -> [V2; KICK_TESTS] {
+		let from = &self.data.kicks[self.state as usize];
+		let to = &rotated.data.kicks[rotated.state as usize];
+		std::array::from_fn(|i| from[i] - to[i])
+	}
+
+	// get the translations to try, in order, when rotating 180 degrees from this state
+	pub fn kicks_180(&self) -> &Kicks180 {
+		&self.data.kicks_180[self.state as usize]
+	}
+
 	// todo add 'make sure it never panics panic! macro'
 	pub fn indexes_of_cells_colliding(&self, direction: data::Flags) -> Vec<usize> {
 //		let mut iter = Iterator::zip(self.current_shape().iter(), self.current_directions().decompress().iter());
@@ -105,7 +125,7 @@ impl Tetromino {
 //	}
 
 	fn rotate(&mut self, side: Side) {
-		self.state = (self.state + side as i8).rem_euclid(self.current_offsets().len() as i8);
+		self.state = (self.state + side as i8).rem_euclid(self.data.rotations.len() as i8);
 //		self.state += if matches!(side, Side::Left) {-1} else {1};
 //		self.state %= self.current_offsets().len();
 	}
@@ -128,3 +148,56 @@ impl From<Shape> for Tetromino {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// the kicks as the SRS tables list them, with y pointing up
+	fn srs(kicks: &[(i32, i32)]) -> Vec<V2> {
+		kicks.iter().map(|&(x, y)| V2::new(x, -y)).collect()
+	}
+
+	// a piece in an SRS state: 0 is the spawn state, 1 is R, 2 is 2 and 3 is L
+	fn in_state(shape: Shape, state: i8) -> Tetromino {
+		let mut piece = Tetromino::from(shape);
+		// the rotations go counter-clockwise, from upside down for every piece but I
+		let spawn = if shape == Shape::I { 0 } else { 2 };
+		piece.set_rotation(spawn - state);
+		piece
+	}
+
+	// the translations tried when turning clockwise
+	fn clockwise_kicks(shape: Shape, state: i8) -> Vec<V2> {
+		let piece = in_state(shape, state);
+		let mut rotated = piece;
+		rotated.rotate_left();
+		piece.kicks_to(&rotated).to_vec()
+	}
+
+	#[test]
+	fn i_kicks_off_the_walls_like_srs() {
+		assert_eq!(clockwise_kicks(Shape::I, 0), srs(&[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]));
+		assert_eq!(clockwise_kicks(Shape::I, 1), srs(&[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]));
+	}
+
+	#[test]
+	fn t_kicks_like_srs_both_ways() {
+		assert_eq!(clockwise_kicks(Shape::T, 0), srs(&[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]));
+		// back from R to the spawn state, counter-clockwise
+		let piece = in_state(Shape::T, 1);
+		let mut rotated = piece;
+		rotated.rotate_right();
+		assert_eq!(piece.kicks_to(&rotated).to_vec(), srs(&[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]));
+	}
+
+	#[test]
+	fn half_turns_kick_like_srs_plus() {
+		let kicks_180 = |shape, state| in_state(shape, state).kicks_180().to_vec();
+		assert_eq!(kicks_180(Shape::T, 0), srs(&[(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]));
+		assert_eq!(kicks_180(Shape::T, 1), srs(&[(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)]));
+		// I turns like the others, though its states sit differently in its rotations
+		assert_eq!(kicks_180(Shape::I, 2), srs(&[(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)]));
+		assert_eq!(kicks_180(Shape::I, 3), srs(&[(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)]));
+	}
+}
@@ -1,10 +1,12 @@
 use crate::tetris::v2::V2;
-use super::offsets::Offsets;
+use super::offsets::{Offsets, Kicks, Kicks180};
 
 // Holds all variants of a Tetromino
 pub struct ShapeData {
 	pub rotations: &'static [Offsets], // all possible rotations
 	pub directions: &'static [MovementFlags], // the direction each cell can move in each rotations
+	pub kicks: &'static [Kicks], // the SRS wall-kick offsets of each rotation
+	pub kicks_180: &'static [Kicks180], // the 180 degree wall-kicks from each rotation
 }
 
 #[derive(Copy, Clone)]
@@ -52,6 +54,7 @@ pub const RIGHT: u8 = 4;
 pub const UP: u8 = 8;
 pub const HORIZONTAL: u8 = 6;
 pub const VERTICAL: u8 = 9;
+#[allow(dead_code)]
 pub const ALL: u8 = 0x0F;
 
 /*
@@ -153,6 +156,54 @@ impl MovementFlags {
 }
 
 
+// SRS wall-kick offsets, one row per rotation in the same order as the rotations below.
+// The rotations here go counter-clockwise and start upside down (except for I), so the rows are
+// the SRS states 2, R, 0, L for J, L, S, T, Z and 0, L, 2, R for I. y points down, unlike in the SRS tables.
+static JLSTZ_KICKS: [Kicks; 4] = [
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 1,y: 0}, V2{x: 1,y: 1}, V2{x: 0,y:-2}, V2{x: 1,y:-2}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x:-1,y: 0}, V2{x:-1,y: 1}, V2{x: 0,y:-2}, V2{x:-1,y:-2}]),
+];
+
+static I_KICKS: [Kicks; 4] = [
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 1,y: 0}, V2{x:-2,y: 0}, V2{x: 1,y: 2}, V2{x:-2,y:-1}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 3,y: 0}, V2{x:-3,y: 0}, V2{x: 3,y: 1}, V2{x:-3,y: 1}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 2,y: 0}, V2{x:-1,y: 0}, V2{x: 2,y:-1}, V2{x:-1,y: 2}]),
+];
+
+// O doesn't move when it rotates, so it never kicks
+static O_KICKS: [Kicks; 4] = [
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+];
+
+// The SRS+ 180 degree wall-kicks, the same for every piece but O. One row per rotation, like the SRS rows above,
+// so the rows are the 180s from 2, R, 0, L for J, L, S, T, Z and from 0, L, 2, R for I
+static JLSTZ_KICKS_180: [Kicks180; 4] = [
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 1}, V2{x:-1,y: 1}, V2{x: 1,y: 1}, V2{x:-1,y: 0}, V2{x: 1,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 1,y: 0}, V2{x: 1,y:-2}, V2{x: 1,y:-1}, V2{x: 0,y:-2}, V2{x: 0,y:-1}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y:-1}, V2{x: 1,y:-1}, V2{x:-1,y:-1}, V2{x: 1,y: 0}, V2{x:-1,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x:-1,y: 0}, V2{x:-1,y:-2}, V2{x:-1,y:-1}, V2{x: 0,y:-2}, V2{x: 0,y:-1}]),
+];
+
+static I_KICKS_180: [Kicks180; 4] = [
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y:-1}, V2{x: 1,y:-1}, V2{x:-1,y:-1}, V2{x: 1,y: 0}, V2{x:-1,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x:-1,y: 0}, V2{x:-1,y:-2}, V2{x:-1,y:-1}, V2{x: 0,y:-2}, V2{x: 0,y:-1}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 1}, V2{x:-1,y: 1}, V2{x: 1,y: 1}, V2{x:-1,y: 0}, V2{x: 1,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 1,y: 0}, V2{x: 1,y:-2}, V2{x: 1,y:-1}, V2{x: 0,y:-2}, V2{x: 0,y:-1}]),
+];
+
+static O_KICKS_180: [Kicks180; 4] = [
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+	Kicks180([V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}, V2{x: 0,y: 0}]),
+];
+
 // All possible phases of allthe tetrominos
 pub static I: ShapeData = ShapeData {
 	rotations: &[
@@ -198,7 +249,9 @@ pub static I: ShapeData = ShapeData {
 		MovementFlags::new(&[UP | HORIZONTAL, HORIZONTAL, HORIZONTAL, HORIZONTAL | DOWN]),
 		MovementFlags::new(&[LEFT | VERTICAL, VERTICAL, VERTICAL, VERTICAL | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, HORIZONTAL, HORIZONTAL, HORIZONTAL | DOWN]),
-	]
+	],
+	kicks: &I_KICKS,
+	kicks_180: &I_KICKS_180,
 };
 
 pub static O: ShapeData = ShapeData{
@@ -210,11 +263,35 @@ pub static O: ShapeData = ShapeData{
 			V2{x: 1,y: 0},
 			V2{x: 0,y: 1},
 			V2{x: 1,y: 1},
-		])
+		]),
+		// the same cells in every rotation, so it can rotate like the rest of the tetrominos
+		Offsets([
+			V2{x: 0,y: 0},
+			V2{x: 1,y: 0},
+			V2{x: 0,y: 1},
+			V2{x: 1,y: 1},
+		]),
+		Offsets([
+			V2{x: 0,y: 0},
+			V2{x: 1,y: 0},
+			V2{x: 0,y: 1},
+			V2{x: 1,y: 1},
+		]),
+		Offsets([
+			V2{x: 0,y: 0},
+			V2{x: 1,y: 0},
+			V2{x: 0,y: 1},
+			V2{x: 1,y: 1},
+		]),
 	],
 	directions: &[
-		MovementFlags::new(&[LEFT | UP, UP | RIGHT, LEFT | DOWN, DOWN | RIGHT])
-	]
+		MovementFlags::new(&[LEFT | UP, UP | RIGHT, LEFT | DOWN, DOWN | RIGHT]),
+		MovementFlags::new(&[LEFT | UP, UP | RIGHT, LEFT | DOWN, DOWN | RIGHT]),
+		MovementFlags::new(&[LEFT | UP, UP | RIGHT, LEFT | DOWN, DOWN | RIGHT]),
+		MovementFlags::new(&[LEFT | UP, UP | RIGHT, LEFT | DOWN, DOWN | RIGHT]),
+	],
+	kicks: &O_KICKS,
+	kicks_180: &O_KICKS_180,
 };
 
 pub static T: ShapeData = ShapeData{
@@ -259,7 +336,9 @@ pub static T: ShapeData = ShapeData{
 		MovementFlags::new(&[HORIZONTAL | UP, LEFT, VERTICAL | RIGHT, HORIZONTAL | DOWN]),
 		MovementFlags::new(&[UP | HORIZONTAL, LEFT | VERTICAL, DOWN, VERTICAL | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, LEFT | VERTICAL, RIGHT, HORIZONTAL | DOWN]),
-	]
+	],
+	kicks: &JLSTZ_KICKS,
+	kicks_180: &JLSTZ_KICKS_180,
 };
 
 pub static L: ShapeData = ShapeData{
@@ -304,7 +383,9 @@ pub static L: ShapeData = ShapeData{
 		MovementFlags::new(&[UP | HORIZONTAL, HORIZONTAL, LEFT | DOWN, VERTICAL | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, LEFT | VERTICAL, VERTICAL, DOWN | RIGHT]),
 		MovementFlags::new(&[LEFT | VERTICAL, UP | RIGHT, HORIZONTAL, HORIZONTAL | DOWN]),
-	]
+	],
+	kicks: &JLSTZ_KICKS,
+	kicks_180: &JLSTZ_KICKS_180,
 };
 
 pub static J: ShapeData = ShapeData{
@@ -349,7 +430,9 @@ pub static J: ShapeData = ShapeData{
 		MovementFlags::new(&[UP | LEFT, VERTICAL | RIGHT, HORIZONTAL, HORIZONTAL | DOWN]),
 		MovementFlags::new(&[HORIZONTAL | UP, LEFT | DOWN, VERTICAL, VERTICAL | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, HORIZONTAL, LEFT | VERTICAL, DOWN | RIGHT]),
-	]
+	],
+	kicks: &JLSTZ_KICKS,
+	kicks_180: &JLSTZ_KICKS_180,
 };
 
 pub static S: ShapeData = ShapeData{
@@ -394,7 +477,9 @@ pub static S: ShapeData = ShapeData{
 		MovementFlags::new(&[UP | HORIZONTAL, LEFT | DOWN, UP | RIGHT, HORIZONTAL | DOWN]),
 		MovementFlags::new(&[UP | LEFT, VERTICAL | RIGHT, LEFT | VERTICAL, DOWN | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, LEFT | DOWN, UP | RIGHT, HORIZONTAL | DOWN]),
-	]
+	],
+	kicks: &JLSTZ_KICKS,
+	kicks_180: &JLSTZ_KICKS_180,
 };
 
 pub static Z: ShapeData = ShapeData{
@@ -439,5 +524,7 @@ pub static Z: ShapeData = ShapeData{
 		MovementFlags::new(&[UP | HORIZONTAL, UP | LEFT, DOWN | RIGHT, HORIZONTAL | DOWN]),
 		MovementFlags::new(&[LEFT | VERTICAL, UP | RIGHT, LEFT | DOWN, VERTICAL | RIGHT]),
 		MovementFlags::new(&[UP | HORIZONTAL, UP | LEFT, DOWN | RIGHT, HORIZONTAL | DOWN]),
-	]
+	],
+	kicks: &JLSTZ_KICKS,
+	kicks_180: &JLSTZ_KICKS_180,
 };
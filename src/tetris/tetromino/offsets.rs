@@ -11,3 +11,31 @@ impl std::ops::Deref for Offsets {
 		self.0.as_ref()
 	}
 }
+
+pub const KICK_TESTS: usize = 5;
+
+// Each item is the offset of a rotation state for one of the SRS wall-kick tests.
+// The translation tried by test i when rotating from state a to state b is a[i] - b[i]
+#[derive(Clone, Copy)]
+pub struct Kicks(pub [V2; KICK_TESTS]);
+
+impl std::ops::Deref for Kicks {
+	type Target = [V2];
+	fn deref(&self) -> &Self::Target {
+		self.0.as_ref()
+	}
+}
+
+pub const KICKS_180_TESTS: usize = 6;
+
+// The translations tried, in order, when rotating 180 degrees from a rotation state.
+// Unlike SRS there's no offset scheme behind them, so they're stored as they're tried
+#[derive(Clone, Copy)]
+pub struct Kicks180(pub [V2; KICKS_180_TESTS]);
+
+impl std::ops::Deref for Kicks180 {
+	type Target = [V2];
+	fn deref(&self) -> &Self::Target {
+		self.0.as_ref()
+	}
+}
@@ -0,0 +1,138 @@
+// a server on a port picked for it, with clients that say what the game would say
+
+use tetris::tetris::{Tetris, game::Game};
+use tetris::tetris::engine::{Engine, FinishReason, GameOverReason, Outcome};
+use tetris::tetris::keybindings::KeyBindings;
+use tetris::tetris::net::{self, Connection, GameSetup, Message, Server, PROTOCOL_VERSION};
+use tetris::tetris::randomizer;
+use tetris::tetris::scoring;
+use tetris::tetris::settings::Settings;
+
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// how long to wait for something that shouldn't come
+const QUIET: Duration = Duration::from_millis(200);
+
+fn start_server() -> String {
+	let server = Server::bind("127.0.0.1:0", Some(42), "7bag".to_string(), "guideline".to_string(), Settings::default()).unwrap();
+	let address = server.local_addr().unwrap().to_string();
+	server.spawn();
+	address
+}
+
+// joins two players at once, since each waits for the other. returns them as players 1 and 2
+fn join_pair(address: &str) -> [(Connection, GameSetup); 2] {
+	let first = {
+		let address = address.to_string();
+		thread::spawn(move || net::join(&address).unwrap())
+	};
+	let second = net::join(address).unwrap();
+	let first = first.join().unwrap();
+	if first.1.player == Some(0) { [first, second] } else { [second, first] }
+}
+
+fn board() -> Message {
+	let mut engine = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 1, Settings::default());
+	engine.reset();
+	Message::Board { player: 7, snapshot: Box::new(engine.board_snapshot()) }
+}
+
+#[test]
+fn handshake_takes_only_the_same_version() {
+	let address = start_server();
+
+	let mut connection = Connection::connect(&address).unwrap();
+	connection.send(&Message::Hello { version: PROTOCOL_VERSION }).unwrap();
+	assert!(matches!(connection.recv_timeout(TIMEOUT), Ok(Message::Hello { version: PROTOCOL_VERSION })));
+
+	let mut connection = Connection::connect(&address).unwrap();
+	connection.send(&Message::Hello { version: PROTOCOL_VERSION + 1 }).unwrap();
+	assert!(matches!(connection.recv_timeout(TIMEOUT), Ok(Message::Error { .. })));
+	assert!(connection.recv_timeout(TIMEOUT).is_err());
+}
+
+#[test]
+fn game_is_relayed_until_it_ends() {
+	let address = start_server();
+	let [(mut first, first_setup), (mut second, second_setup)] = join_pair(&address);
+	assert_eq!(first_setup.player, Some(0));
+	assert_eq!(second_setup.player, Some(1));
+	assert_eq!(first_setup.seed, 42);
+	assert_eq!(second_setup.seed, 42);
+	let (spectator, spectator_setup) = net::watch(&address).unwrap();
+	assert_eq!(spectator_setup.player, None);
+
+	// garbage goes to the opponent only, the board to everyone else with the player it came from
+	first.send(&Message::Garbage { rows: 3 }).unwrap();
+	first.send(&board()).unwrap();
+	assert!(matches!(second.recv_timeout(TIMEOUT), Ok(Message::Garbage { rows: 3 })));
+	assert!(matches!(second.recv_timeout(TIMEOUT), Ok(Message::Board { player: 0, .. })));
+	assert!(matches!(spectator.recv_timeout(TIMEOUT), Ok(Message::Board { player: 0, .. })));
+
+	second.send(&board()).unwrap();
+	assert!(matches!(first.recv_timeout(TIMEOUT), Ok(Message::Board { player: 1, .. })));
+	assert!(matches!(spectator.recv_timeout(TIMEOUT), Ok(Message::Board { player: 1, .. })));
+
+	// the game ends with the first game over, and the server lets go of everyone in it
+	second.send(&Message::GameOver { player: 0, outcome: Outcome::GameOver(GameOverReason::TopOut), score: 0 }).unwrap();
+	let over = |message| matches!(message, Ok(Message::GameOver { player: 1, outcome: Outcome::GameOver(GameOverReason::TopOut), .. }));
+	assert!(over(first.recv_timeout(TIMEOUT)));
+	assert!(over(spectator.recv_timeout(TIMEOUT)));
+	first.send(&Message::Garbage { rows: 1 }).unwrap();
+	assert!(matches!(second.recv_timeout(TIMEOUT), Err(e) if e == "The connection was closed"));
+	assert!(matches!(spectator.recv_timeout(QUIET), Err(e) if e == "The connection was closed"));
+}
+
+#[test]
+fn time_up_waits_for_both_scores() {
+	let address = start_server();
+	let [(mut first, _), (mut second, _)] = join_pair(&address);
+	let time_up = |score| Message::GameOver { player: 0, outcome: Outcome::Finished(FinishReason::TimeUp), score };
+
+	first.send(&time_up(100)).unwrap();
+	assert!(matches!(second.recv_timeout(TIMEOUT), Ok(Message::GameOver { player: 0, score: 100, .. })));
+	// the game is on until the other player's time runs out too
+	second.send(&time_up(200)).unwrap();
+	assert!(matches!(first.recv_timeout(TIMEOUT), Ok(Message::GameOver { player: 1, score: 200, .. })));
+	assert!(matches!(second.recv_timeout(TIMEOUT), Err(e) if e == "The connection was closed"));
+}
+
+#[test]
+fn games_run_side_by_side() {
+	let address = start_server();
+	let [(mut first, _), (second, _)] = join_pair(&address);
+	// the first game is still on while the next pair starts theirs
+	let [(third, third_setup), (fourth, fourth_setup)] = join_pair(&address);
+	assert_eq!(third_setup.player, Some(0));
+	assert_eq!(fourth_setup.player, Some(1));
+
+	first.send(&Message::Garbage { rows: 2 }).unwrap();
+	assert!(matches!(second.recv_timeout(TIMEOUT), Ok(Message::Garbage { rows: 2 })));
+	assert!(matches!(third.recv_timeout(QUIET), Err(e) if e == "Timed out waiting for a message"));
+	assert!(matches!(fourth.recv_timeout(QUIET), Err(e) if e == "Timed out waiting for a message"));
+}
+
+#[test]
+fn spectator_sees_the_boards_until_the_game_is_decided() {
+	let address = start_server();
+	let [(mut first, _), (mut second, _)] = join_pair(&address);
+	let (spectator, setup) = net::watch(&address).unwrap();
+
+	// a board of another size than the game's is left out
+	let mut narrow = Engine::new(randomizer::from_name("7bag").unwrap(), scoring::from_name("guideline").unwrap(), 1, Settings { width: 4, ..Settings::default() });
+	narrow.reset();
+	first.send(&Message::Board { player: 0, snapshot: Box::new(narrow.board_snapshot()) }).unwrap();
+	second.send(&board()).unwrap();
+	second.send(&Message::GameOver { player: 1, outcome: Outcome::GameOver(GameOverReason::TopOut), score: 0 }).unwrap();
+
+	let engine = || Engine::new(setup.make_randomizer().unwrap(), setup.make_scoring().unwrap(), setup.seed, setup.settings.clone());
+	let mut game = Tetris::new(Vec::new(), std::io::empty(), KeyBindings::default(), setup.make_randomizer().unwrap(), setup.make_scoring().unwrap(), setup.seed, setup.settings.clone())
+		.with_opponent(engine(), KeyBindings::default())
+		.with_spectator(spectator);
+	game.reset();
+	game.run();
+	assert_eq!(game.remote_result(), Some("Player 1 wins"));
+	assert!(game.engines().all(|engine| engine.width() == setup.settings.width));
+}